@@ -0,0 +1,69 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use minigrep::{search, search_insensitive};
+
+// 改写前的逐行实现，作为对照
+fn search_by_line<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| line.contains(query))
+        .collect()
+}
+
+fn search_insensitive_by_line<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let query = query.to_lowercase();
+
+    contents
+        .lines()
+        .filter(|line| line.to_lowercase().contains(&query))
+        .collect()
+}
+
+// 将示例诗句重复到约 16 MiB，并在末尾附近放一行罕见内容
+fn large_input(poem: &str) -> String {
+    let mut contents = String::with_capacity(16 << 20);
+    while contents.len() < 16 << 20 {
+        contents.push_str(poem);
+    }
+    contents.push_str("Needle in a haystack\n");
+    contents.push_str(poem);
+    contents
+}
+
+fn bench_search(c: &mut Criterion) {
+    let poem = include_str!("../sample.txt");
+    // 每节开头加一行非 ASCII 文本，忽略大小写搜索 ASCII 查询时也应保持缓冲区扫描
+    let accented = poem.replace("I'm nobody!", "Ça va ? Ñandú!\nI'm nobody!");
+    assert_ne!(poem, accented);
+
+    for (input, poem) in [("ascii", poem), ("non_ascii", accented.as_str())] {
+        let contents = large_input(poem);
+        bench_input(c, input, &contents);
+    }
+}
+
+// 罕见查询体现扫描速度，常见查询体现行边界定位的开销
+fn bench_input(c: &mut Criterion, input: &str, contents: &str) {
+    for query in ["haystack", "nobody"] {
+        let id = format!("{}/{}", input, query);
+        let mut group = c.benchmark_group("search");
+        group.bench_with_input(BenchmarkId::new("by_line", &id), query, |b, q| {
+            b.iter(|| search_by_line(black_box(q), black_box(contents)))
+        });
+        group.bench_with_input(BenchmarkId::new("buffer", &id), query, |b, q| {
+            b.iter(|| search(black_box(q), black_box(contents)))
+        });
+        group.finish();
+
+        let mut group = c.benchmark_group("search_insensitive");
+        group.bench_with_input(BenchmarkId::new("by_line", &id), query, |b, q| {
+            b.iter(|| search_insensitive_by_line(black_box(q), black_box(contents)))
+        });
+        group.bench_with_input(BenchmarkId::new("buffer", &id), query, |b, q| {
+            b.iter(|| search_insensitive(black_box(q), black_box(contents)))
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_search);
+criterion_main!(benches);
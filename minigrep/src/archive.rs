@@ -0,0 +1,366 @@
+// 归档文件（.tar、.tar.gz/.tgz、.zip）的成员读取：每个成员作为一个虚拟文件搜索，
+// 名字写作 archive.zip!/inner/path。嵌套的归档会继续展开
+
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use std::io::{self, Read};
+
+// 防止恶意构造的归档无限嵌套
+const MAX_DEPTH: usize = 4;
+
+// 单个成员（以及整个 .tar.gz 流）解压后的最大字节数，防止压缩炸弹耗尽内存
+const MAX_EXPANDED: usize = 256 << 20;
+
+const BLOCK: usize = 512;
+
+// 成员读取失败（例如解压后超过大小限制）时 data 为错误，其余成员不受影响
+pub struct Member {
+    pub name: String,
+    pub data: io::Result<Vec<u8>>,
+}
+
+fn corrupt(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("corrupt {} archive", what),
+    )
+}
+
+fn too_large() -> io::Error {
+    io::Error::other(format!(
+        "expands to more than {} MiB, skipped",
+        MAX_EXPANDED >> 20
+    ))
+}
+
+// 最多读取 limit 字节，超出时返回错误而不是继续分配内存
+fn read_limited(reader: impl Read, limit: usize, capacity: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(capacity.min(limit));
+    reader.take(limit as u64 + 1).read_to_end(&mut out)?;
+    if out.len() > limit {
+        return Err(too_large());
+    }
+    Ok(out)
+}
+
+pub fn is_archive(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    [".tar", ".tar.gz", ".tgz", ".zip"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+// 展开归档中的所有普通文件，name 为归档自身显示的名字
+pub fn members(name: &str, data: &[u8]) -> io::Result<Vec<Member>> {
+    let mut out = Vec::new();
+    expand(name, data, 0, &mut out)?;
+    Ok(out)
+}
+
+fn expand(name: &str, data: &[u8], depth: usize, out: &mut Vec<Member>) -> io::Result<()> {
+    let lower = name.to_ascii_lowercase();
+    let entries = if lower.ends_with(".zip") {
+        zip_entries(data)?
+    } else if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+        let tar = read_limited(MultiGzDecoder::new(data), MAX_EXPANDED, 0)?;
+        tar_entries(&tar)?
+    } else {
+        tar_entries(data)?
+    };
+
+    for (inner, data) in entries {
+        let full = format!("{}!/{}", name, inner);
+        // 嵌套的归档读不了时当作普通成员搜索，不影响其余成员
+        if let (Ok(bytes), true) = (&data, depth < MAX_DEPTH && is_archive(&inner)) {
+            let mut nested = Vec::new();
+            match expand(&full, bytes, depth + 1, &mut nested) {
+                Ok(()) => out.extend(nested),
+                Err(_) => out.push(Member { name: full, data }),
+            }
+        } else {
+            out.push(Member { name: full, data });
+        }
+    }
+
+    Ok(())
+}
+
+// 八进制数字段，首字节最高位为 1 时是 GNU 的 base-256 编码
+fn tar_number(field: &[u8]) -> io::Result<usize> {
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        return Ok(field[1..]
+            .iter()
+            .fold(0usize, |n, &b| (n << 8) | b as usize));
+    }
+
+    let text = std::str::from_utf8(field).map_err(|_| corrupt("tar"))?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    usize::from_str_radix(text, 8).map_err(|_| corrupt("tar"))
+}
+
+fn tar_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn tar_entries(data: &[u8]) -> io::Result<Vec<(String, io::Result<Vec<u8>>)>> {
+    let mut entries = Vec::new();
+    // GNU 长文件名（L）和 pax 头（x）会覆盖下一项的名字
+    let mut long_name = None;
+    let mut pos = 0;
+
+    while pos + BLOCK <= data.len() {
+        let header = &data[pos..pos + BLOCK];
+        // 全零的块表示归档结束
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let size = tar_number(&header[124..136])?;
+        // 构造的大小可能溢出
+        let start = pos + BLOCK;
+        let end = start.checked_add(size).ok_or_else(|| corrupt("tar"))?;
+        let body = data.get(start..end).ok_or_else(|| corrupt("tar"))?;
+        pos = start + size.div_ceil(BLOCK) * BLOCK;
+
+        match header[156] {
+            b'L' => long_name = Some(tar_string(body)),
+            b'x' => {
+                // pax 记录形如 "<长度> path=<路径>\n"
+                long_name = String::from_utf8_lossy(body).lines().find_map(|record| {
+                    let (_, kv) = record.split_once(' ')?;
+                    kv.strip_prefix("path=").map(String::from)
+                });
+            }
+            b'0' | 0 | b'7' => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let name = tar_string(&header[0..100]);
+                    let prefix = tar_string(&header[345..500]);
+                    // ustar 格式的路径可以分成前缀和名字两部分
+                    if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                        format!("{}/{}", prefix, name)
+                    } else {
+                        name
+                    }
+                });
+                entries.push((name, Ok(body.to_vec())));
+            }
+            // 目录、链接、全局 pax 头等不包含文件内容
+            _ => long_name = None,
+        }
+    }
+
+    Ok(entries)
+}
+
+fn u16_at(data: &[u8], pos: usize) -> io::Result<usize> {
+    let bytes = data.get(pos..pos + 2).ok_or_else(|| corrupt("zip"))?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+fn u32_at(data: &[u8], pos: usize) -> io::Result<usize> {
+    let bytes = data.get(pos..pos + 4).ok_or_else(|| corrupt("zip"))?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+}
+
+// 从文件末尾的中央目录读取成员列表，只支持存储（0）和 deflate（8）两种压缩方式
+fn zip_entries(data: &[u8]) -> io::Result<Vec<(String, io::Result<Vec<u8>>)>> {
+    // 中央目录结束记录在末尾，后面最多跟 65535 字节的注释
+    let search_from = data.len().saturating_sub(22 + 0xffff);
+    let end = (search_from..data.len().saturating_sub(21))
+        .rev()
+        .find(|&i| data[i..i + 4] == [0x50, 0x4b, 0x05, 0x06])
+        .ok_or_else(|| corrupt("zip"))?;
+
+    let count = u16_at(data, end + 10)?;
+    let mut pos = u32_at(data, end + 16)?;
+    if pos == 0xffff_ffff {
+        return Err(io::Error::other("zip64 archives are not supported"));
+    }
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        if u32_at(data, pos)? != 0x0201_4b50 {
+            return Err(corrupt("zip"));
+        }
+        let method = u16_at(data, pos + 10)?;
+        let compressed = u32_at(data, pos + 20)?;
+        let size = u32_at(data, pos + 24)?;
+        let name_len = u16_at(data, pos + 28)?;
+        let extra_len = u16_at(data, pos + 30)?;
+        let comment_len = u16_at(data, pos + 32)?;
+        let local = u32_at(data, pos + 42)?;
+        let name = data
+            .get(pos + 46..pos + 46 + name_len)
+            .ok_or_else(|| corrupt("zip"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        pos += 46 + name_len + extra_len + comment_len;
+
+        if name.ends_with('/') {
+            continue;
+        }
+
+        // 本地文件头的扩展字段长度可能与中央目录中的不同
+        let start = local + 30 + u16_at(data, local + 26)? + u16_at(data, local + 28)?;
+        let body = data
+            .get(start..start + compressed)
+            .ok_or_else(|| corrupt("zip"))?;
+        let contents = match method {
+            0 => Ok(body.to_vec()),
+            // 头部声明的大小不可信：超过限制的直接跳过，否则也只按限制读取
+            8 if size > MAX_EXPANDED => Err(too_large()),
+            8 => read_limited(DeflateDecoder::new(body), MAX_EXPANDED, size),
+            _ => {
+                return Err(io::Error::other(format!(
+                    "unsupported zip compression method {} for {}",
+                    method, name
+                )))
+            }
+        };
+        entries.push((name, contents));
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    fn tar_header(name: &str, size: usize, kind: u8) -> Vec<u8> {
+        let mut header = vec![0; BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}\0", size);
+        header[124..136].copy_from_slice(size.as_bytes());
+        header[156] = kind;
+        header
+    }
+
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut tar = Vec::new();
+        for (name, data) in files {
+            tar.extend(tar_header(name, data.len(), b'0'));
+            tar.extend_from_slice(data);
+            tar.resize(tar.len().div_ceil(BLOCK) * BLOCK, 0);
+        }
+        tar.extend(vec![0; BLOCK * 2]);
+        tar
+    }
+
+    // 只使用存储方式的最小 zip
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = Vec::new();
+        let mut central = Vec::new();
+        for (name, data) in files {
+            let offset = zip.len() as u32;
+            zip.extend([0x50, 0x4b, 0x03, 0x04]);
+            zip.extend([0; 14]);
+            zip.extend((data.len() as u32).to_le_bytes());
+            zip.extend((data.len() as u32).to_le_bytes());
+            zip.extend((name.len() as u16).to_le_bytes());
+            zip.extend([0, 0]);
+            zip.extend(name.as_bytes());
+            zip.extend_from_slice(data);
+
+            central.extend([0x50, 0x4b, 0x01, 0x02]);
+            central.extend([0; 16]);
+            central.extend((data.len() as u32).to_le_bytes());
+            central.extend((data.len() as u32).to_le_bytes());
+            central.extend((name.len() as u16).to_le_bytes());
+            central.extend([0; 12]);
+            central.extend(offset.to_le_bytes());
+            central.extend(name.as_bytes());
+        }
+        let central_offset = zip.len() as u32;
+        zip.extend(&central);
+        zip.extend([0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+        zip.extend((files.len() as u16).to_le_bytes());
+        zip.extend((files.len() as u16).to_le_bytes());
+        zip.extend((central.len() as u32).to_le_bytes());
+        zip.extend(central_offset.to_le_bytes());
+        zip.extend([0, 0]);
+        zip
+    }
+
+    fn names(members: &[Member]) -> Vec<&str> {
+        members.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_tar_and_gzip() {
+        let tar = tar(&[("logs/app.log", b"error: boom\n"), ("README", b"hi")]);
+        let found = members("bundle.tar", &tar).unwrap();
+        assert_eq!(
+            vec!["bundle.tar!/logs/app.log", "bundle.tar!/README"],
+            names(&found)
+        );
+        assert_eq!(b"error: boom\n".to_vec(), *found[0].data.as_ref().unwrap());
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&tar).unwrap();
+        let found = members("bundle.tgz", &gz.finish().unwrap()).unwrap();
+        assert_eq!(b"hi".to_vec(), *found[1].data.as_ref().unwrap());
+    }
+
+    #[test]
+    fn reads_nested_zip() {
+        let inner = tar(&[("deep.txt", b"needle")]);
+        let support = zip(&[("a.txt", b"hello"), ("dir/inner.tar", &inner)]);
+
+        let found = members("support.zip", &support).unwrap();
+        assert_eq!(
+            vec!["support.zip!/a.txt", "support.zip!/dir/inner.tar!/deep.txt"],
+            names(&found)
+        );
+        assert_eq!(b"needle".to_vec(), *found[1].data.as_ref().unwrap());
+
+        assert!(members("broken.zip", b"not a zip").is_err());
+
+        // 读不了的嵌套归档作为普通成员保留
+        let outer = zip(&[("fake.zip", b"plain text")]);
+        let found = members("outer.zip", &outer).unwrap();
+        assert_eq!(vec!["outer.zip!/fake.zip"], names(&found));
+    }
+
+    #[test]
+    fn limits_expanded_sizes() {
+        // 1 MiB 的零压缩后只有约 1 KiB，按 64 KiB 的限制读取时报错
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&vec![0; 1 << 20]).unwrap();
+        let gz = gz.finish().unwrap();
+        assert!(read_limited(MultiGzDecoder::new(&gz[..]), 64 << 10, 0).is_err());
+        assert_eq!(
+            1 << 20,
+            read_limited(MultiGzDecoder::new(&gz[..]), 1 << 20, 0)
+                .unwrap()
+                .len()
+        );
+
+        // 声明的大小超过限制的 deflate 成员不分配内存，只有该成员报错
+        let mut support = zip(&[("bomb.txt", b"x"), ("ok.txt", b"fine")]);
+        let central = u32_at(&support, support.len() - 6).unwrap();
+        support[central + 10] = 8;
+        support[central + 24..central + 28].copy_from_slice(&u32::MAX.to_le_bytes());
+        let found = members("support.zip", &support).unwrap();
+        assert_eq!(
+            vec!["support.zip!/bomb.txt", "support.zip!/ok.txt"],
+            names(&found)
+        );
+        assert!(found[0].data.is_err());
+        assert_eq!(b"fine".to_vec(), *found[1].data.as_ref().unwrap());
+    }
+
+    #[test]
+    fn rejects_overflowing_tar_sizes() {
+        // base-256 编码的最大大小，start + size 会溢出
+        let mut tar = tar_header("huge", 0, b'0');
+        tar[124] = 0x80;
+        tar[125..136].fill(0xff);
+        tar.extend(vec![0; BLOCK]);
+        assert!(members("huge.tar", &tar).is_err());
+    }
+}
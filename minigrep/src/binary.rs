@@ -0,0 +1,58 @@
+// 二进制文件的检测与处理策略，行为参照 grep 的 --binary-files 选项
+
+// 与 grep 一致，只检查文件开头的一个块
+const BLOCK_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinaryFiles {
+    // 默认：只报告 "Binary file ... matches"
+    #[default]
+    Binary,
+    // 当作文本搜索
+    Text,
+    // 直接跳过二进制文件
+    WithoutMatch,
+}
+
+impl BinaryFiles {
+    pub fn parse(value: &str) -> Result<BinaryFiles, &'static str> {
+        match value {
+            "binary" => Ok(BinaryFiles::Binary),
+            "text" => Ok(BinaryFiles::Text),
+            "without-match" => Ok(BinaryFiles::WithoutMatch),
+            _ => Err("--binary-files must be one of: binary, text, without-match"),
+        }
+    }
+}
+
+// 第一个块中出现 NUL 字节即视为二进制内容
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let block = &bytes[..bytes.len().min(BLOCK_SIZE)];
+    block.contains(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_nul_in_first_block() {
+        assert!(!is_binary(b"Rust:\nsafe, fast, productive.\n"));
+        assert!(is_binary(b"ELF\0\x01\x02"));
+
+        // NUL 出现在第一个块之后则不算
+        let mut late = vec![b'a'; BLOCK_SIZE];
+        late.push(0);
+        assert!(!is_binary(&late));
+    }
+
+    #[test]
+    fn parses_policy() {
+        assert_eq!(Ok(BinaryFiles::Text), BinaryFiles::parse("text"));
+        assert_eq!(
+            Ok(BinaryFiles::WithoutMatch),
+            BinaryFiles::parse("without-match")
+        );
+        assert!(BinaryFiles::parse("skip").is_err());
+    }
+}
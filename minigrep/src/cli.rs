@@ -0,0 +1,451 @@
+// 声明式的命令行定义：Config::build 的解析、shell 补全脚本和 man 手册
+// 都从同一张 FLAGS 表生成，选项与文档不会脱节
+
+use std::collections::HashMap;
+
+pub struct Flag {
+    pub long: &'static str,
+    pub short: Option<char>,
+    // 需要取值的选项写明值的名称，例如 TYPE
+    pub value: Option<&'static str>,
+    // 值的可选项，用于补全提示和手册
+    pub choices: &'static [&'static str],
+    // 可以重复出现，每次的值都保留
+    pub multiple: bool,
+    pub help: &'static str,
+}
+
+const fn switch(long: &'static str, help: &'static str) -> Flag {
+    Flag {
+        long,
+        short: None,
+        value: None,
+        choices: &[],
+        multiple: false,
+        help,
+    }
+}
+
+const fn option(long: &'static str, value: &'static str, help: &'static str) -> Flag {
+    Flag {
+        long,
+        short: None,
+        value: Some(value),
+        choices: &[],
+        multiple: false,
+        help,
+    }
+}
+
+pub const FLAGS: &[Flag] = &[
+    Flag {
+        choices: &["binary", "text", "without-match"],
+        ..option(
+            "binary-files",
+            "TYPE",
+            "How to treat files with NUL bytes in the first block",
+        )
+    },
+    Flag {
+        choices: &["path", "modified", "none"],
+        ..option(
+            "sort",
+            "ORDER",
+            "Order in which files are searched and printed",
+        )
+    },
+    switch("unique", "Suppress duplicate output lines across all files"),
+    Flag {
+        short: Some('o'),
+        ..switch(
+            "only-matching",
+            "Print only the matched text of each occurrence",
+        )
+    },
+    switch("csv", "Parse input as CSV records with quoting"),
+    switch("tsv", "Parse input as TSV records with quoting"),
+    Flag {
+        multiple: true,
+        ..option(
+            "field",
+            "NAME",
+            "Only match in this CSV/TSV column (repeatable)",
+        )
+    },
+    option(
+        "json-path",
+        "PATH",
+        "Only match the selected field of each JSON line, e.g. $.level",
+    ),
+    option(
+        "since",
+        "TIME",
+        "Only search lines timestamped at or after TIME",
+    ),
+    option(
+        "until",
+        "TIME",
+        "Only search lines timestamped at or before TIME",
+    ),
+    option(
+        "time-format",
+        "FORMAT",
+        "strftime format of the timestamp at the start of each line",
+    ),
+    switch(
+        "time-sorted",
+        "Timestamps are ascending, binary-search the time window (the file is still read whole)",
+    ),
+    option(
+        "pre",
+        "COMMAND",
+        "Search the output of COMMAND run with each file path",
+    ),
+    Flag {
+        multiple: true,
+        ..option(
+            "pre-glob",
+            "GLOB",
+            "Only preprocess files matching GLOB (repeatable)",
+        )
+    },
+    switch("bool", "Parse the query as an expression with AND, OR, NOT"),
+    switch("paragraph", "Match blank-line separated paragraphs"),
+    option(
+        "record-separator",
+        "SEP",
+        "Match records starting at lines matching the regex SEP, or blank",
+    ),
+    switch(
+        "bytes",
+        "Parse the query as hex bytes with ?? wildcards and search raw bytes",
+    ),
+    option(
+        "rev",
+        "REV",
+        "Search files as of a git revision, e.g. HEAD~10, in the current repository",
+    ),
+    switch(
+        "all-revs",
+        "Search files in every commit reachable from any git ref",
+    ),
+    switch("stats", "Print statistics after searching"),
+    Flag {
+        choices: &["completions", "man"],
+        ..option(
+            "generate",
+            "KIND",
+            "Print shell completions (followed by bash, zsh or fish) or a man page",
+        )
+    },
+];
+
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+// 解析结果：每个选项出现过的值（开关记为空字符串），以及位置参数
+#[derive(Debug, Default)]
+pub struct Matches {
+    values: HashMap<&'static str, Vec<String>>,
+    pub positional: Vec<String>,
+}
+
+impl Matches {
+    pub fn flag(&self, long: &str) -> bool {
+        self.values.contains_key(long)
+    }
+
+    // 多次出现时以最后一次为准
+    pub fn value(&self, long: &str) -> Option<&str> {
+        self.values.get(long)?.last().map(String::as_str)
+    }
+
+    pub fn values(&self, long: &str) -> Vec<String> {
+        self.values.get(long).cloned().unwrap_or_default()
+    }
+}
+
+// 支持 --flag value、--flag=value、短选项 -o，以及用 -- 结束选项。
+// 没有声明对应短选项的 -5、-x 等参数按位置参数处理，方便搜索以 - 开头的文本
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Matches, String> {
+    let mut matches = Matches::default();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            matches.positional.extend(args);
+            break;
+        }
+
+        let (flag, inline) = if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let flag = FLAGS
+                .iter()
+                .find(|flag| flag.long == name)
+                .ok_or_else(|| unknown(&arg))?;
+            (flag, inline)
+        } else if let Some(flag) = short_flag(&arg) {
+            // 声明过的短选项后面不能再跟其他字符
+            if arg.len() != 2 {
+                return Err(unknown(&arg));
+            }
+            (flag, None)
+        } else {
+            matches.positional.push(arg);
+            continue;
+        };
+
+        let value = match flag.value {
+            Some(_) => inline
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("--{} needs a value", flag.long))?,
+            None if inline.is_some() => return Err(format!("--{} takes no value", flag.long)),
+            None => String::new(),
+        };
+        matches.values.entry(flag.long).or_default().push(value);
+    }
+
+    Ok(matches)
+}
+
+fn short_flag(arg: &str) -> Option<&'static Flag> {
+    let short = arg.strip_prefix('-')?.chars().next()?;
+    FLAGS.iter().find(|flag| flag.short == Some(short))
+}
+
+fn unknown(arg: &str) -> String {
+    let name = arg.split_once('=').map_or(arg, |(name, _)| name);
+    format!(
+        "unknown option {} (put -- before a query that starts with -)",
+        name
+    )
+}
+
+// 带引号的帮助文本需要转义单引号
+fn quote(help: &str) -> String {
+    help.replace('\'', "'\\''")
+}
+
+pub fn completions(shell: &str) -> Result<String, String> {
+    match shell {
+        "bash" => Ok(bash()),
+        "zsh" => Ok(zsh()),
+        "fish" => Ok(fish()),
+        _ => Err(format!(
+            "unsupported shell {}, expected one of: {}",
+            shell,
+            SHELLS.join(", ")
+        )),
+    }
+}
+
+fn bash() -> String {
+    let mut words: Vec<String> = Vec::new();
+    let mut cases = String::new();
+    for flag in FLAGS {
+        words.push(format!("--{}", flag.long));
+        if let Some(short) = flag.short {
+            words.push(format!("-{}", short));
+        }
+        if flag.value.is_none() {
+            continue;
+        }
+        let reply = if flag.choices.is_empty() {
+            String::from("COMPREPLY=()")
+        } else {
+            format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                flag.choices.join(" ")
+            )
+        };
+        cases.push_str(&format!(
+            "        --{})\n            {}\n            return\n            ;;\n",
+            flag.long, reply
+        ));
+    }
+
+    format!(
+        r#"_minigrep() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+
+    case "$prev" in
+{cases}    esac
+
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{words}" -- "$cur"))
+        return
+    fi
+
+    COMPREPLY=($(compgen -f -- "$cur"))
+}}
+
+complete -o filenames -F _minigrep minigrep
+"#,
+        cases = cases,
+        words = words.join(" ")
+    )
+}
+
+fn zsh() -> String {
+    let mut specs = String::new();
+    for flag in FLAGS {
+        let help = quote(flag.help)
+            .replace('[', "\\[")
+            .replace(']', "\\]")
+            .replace(':', "\\:");
+        let spec = match flag.value {
+            Some(value) => format!("=[{}]:{}:({})", help, value, flag.choices.join(" ")),
+            None => format!("[{}]", help),
+        };
+        let repeat = if flag.multiple { "*" } else { "" };
+        let line = match flag.short {
+            Some(short) => format!(
+                "'{repeat}(-{short} --{long})'{{-{short},--{long}}}'{spec}'",
+                repeat = repeat,
+                short = short,
+                long = flag.long,
+                spec = spec
+            ),
+            None => format!("'{}--{}{}'", repeat, flag.long, spec),
+        };
+        specs.push_str(&format!("    {} \\\n", line));
+    }
+
+    format!(
+        "#compdef minigrep\n\n_arguments -s \\\n{}    '1:query:' \\\n    '*:path:_files'\n",
+        specs
+    )
+}
+
+fn fish() -> String {
+    let mut script = String::new();
+    for flag in FLAGS {
+        script.push_str(&format!("complete -c minigrep -l {}", flag.long));
+        if let Some(short) = flag.short {
+            script.push_str(&format!(" -s {}", short));
+        }
+        if flag.value.is_some() {
+            if flag.choices.is_empty() {
+                script.push_str(" -r");
+            } else {
+                script.push_str(&format!(" -x -a '{}'", flag.choices.join(" ")));
+            }
+        }
+        script.push_str(&format!(" -d '{}'\n", quote(flag.help)));
+    }
+    script
+}
+
+// roff 中的 - 和 \ 需要转义
+fn roff(text: &str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+pub fn man_page() -> String {
+    let mut page = format!(
+        ".TH MINIGREP 1 \"\" \"minigrep {}\"\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    page.push_str(".SH NAME\nminigrep \\- search files for lines containing a query\n");
+    page.push_str(".SH SYNOPSIS\n.B minigrep\n[\\fIOPTIONS\\fR] \\fIQUERY\\fR \\fIPATH\\fR...\n");
+    page.push_str(
+        ".SH DESCRIPTION\nPrint the lines of each \\fIPATH\\fR that contain \\fIQUERY\\fR. \
+Directories are searched recursively. \
+Arguments after \\fB\\-\\-\\fR are never treated as options, so \\fBminigrep \\-\\- \\-\\-verbose log\\fR \
+searches for \\-\\-verbose. An argument such as \\fB\\-5\\fR that matches no short option is \
+also taken as the query or a path.\n",
+    );
+
+    page.push_str(".SH OPTIONS\n");
+    for flag in FLAGS {
+        page.push_str(".TP\n");
+        if let Some(short) = flag.short {
+            page.push_str(&format!("\\fB\\-{}\\fR, ", short));
+        }
+        page.push_str(&format!("\\fB\\-\\-{}\\fR", roff(flag.long)));
+        if let Some(value) = flag.value {
+            page.push_str(&format!(" \\fI{}\\fR", value));
+        }
+        page.push('\n');
+        page.push_str(&roff(flag.help));
+        if !flag.choices.is_empty() {
+            page.push_str(&format!(
+                ". Possible values: {}",
+                roff(&flag.choices.join(", "))
+            ));
+        }
+        page.push_str(".\n");
+    }
+
+    page.push_str(
+        ".SH ENVIRONMENT\n.TP\n.B CASE_SENSITIVE\nWhen set, matching is case sensitive.\n",
+    );
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        list.iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn parses_declared_flags() {
+        let matches = parse(args(&[
+            "--sort=path",
+            "-o",
+            "--field",
+            "a",
+            "q",
+            "--field=b",
+            "--",
+            "--csv",
+        ]))
+        .unwrap();
+
+        assert_eq!(Some("path"), matches.value("sort"));
+        assert!(matches.flag("only-matching"));
+        assert!(!matches.flag("csv"));
+        assert_eq!(vec!["a", "b"], matches.values("field"));
+        assert_eq!(vec!["q", "--csv"], matches.positional);
+
+        assert!(parse(args(&["--nope"])).is_err());
+        assert!(parse(args(&["-ox"])).is_err());
+        assert!(parse(args(&["--sort"])).is_err());
+        assert!(parse(args(&["--csv=1"])).is_err());
+    }
+
+    #[test]
+    fn undeclared_short_arguments_are_positional() {
+        let matches = parse(args(&["-5", "-x", "-o"])).unwrap();
+        assert_eq!(vec!["-5", "-x"], matches.positional);
+        assert!(matches.flag("only-matching"));
+        assert_eq!(vec!["-"], parse(args(&["-"])).unwrap().positional);
+    }
+
+    #[test]
+    fn generated_docs_cover_every_flag() {
+        let docs = [
+            completions("bash").unwrap(),
+            completions("zsh").unwrap(),
+            completions("fish").unwrap(),
+            man_page(),
+        ];
+
+        for flag in FLAGS {
+            assert!(docs[0].contains(&format!("--{}", flag.long)));
+            assert!(docs[1].contains(&format!("--{}", flag.long)));
+            assert!(docs[2].contains(&format!("-l {}", flag.long)));
+            assert!(docs[3].contains(&format!("\\-\\-{}", roff(flag.long))));
+        }
+        assert!(completions("powershell").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+// CSV/TSV 记录解析：支持引号包裹的字段（字段内可含分隔符、换行和 "" 转义），
+// 只在指定的列中匹配查询，避免跨列误匹配
+
+pub struct Record<'a> {
+    // 记录的原始文本（不含行尾换行符），匹配时原样打印
+    pub raw: &'a str,
+    pub fields: Vec<String>,
+}
+
+// 引号直到文件末尾都没有闭合时返回错误，否则后面的所有行都会被吞进一个字段
+pub fn parse_records(contents: &str, delimiter: char) -> Result<Vec<Record<'_>>, String> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    // 只有出现在字段开头的 " 才开始引号字段，字段中间的 " 按普通字符处理
+    let mut field_start = true;
+    let mut start = 0;
+    // 当前行号和最近一个引号字段开始的行号，用于报错
+    let mut line = 1;
+    let mut quote_line = 0;
+
+    let mut chars = contents.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '\n' {
+            line += 1;
+        }
+        if in_quotes {
+            match c {
+                '"' if chars.peek().map(|&(_, next)| next) == Some('"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        let at_start = std::mem::replace(&mut field_start, false);
+        match c {
+            '"' if at_start => {
+                in_quotes = true;
+                quote_line = line;
+            }
+            '\n' => {
+                field_start = true;
+                fields.push(std::mem::take(&mut field));
+                let raw = contents[start..i]
+                    .strip_suffix('\r')
+                    .unwrap_or(&contents[start..i]);
+                records.push(Record {
+                    raw,
+                    fields: std::mem::take(&mut fields),
+                });
+                start = i + 1;
+            }
+            // \r\n 中的 \r 不属于字段内容
+            '\r' if chars.peek().map(|&(_, next)| next) == Some('\n') => (),
+            c if c == delimiter => {
+                field_start = true;
+                fields.push(std::mem::take(&mut field));
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(format!(
+            "unterminated quoted field starting on line {}",
+            quote_line
+        ));
+    }
+
+    // 最后一条记录可能没有换行结尾
+    if start < contents.len() {
+        fields.push(field);
+        records.push(Record {
+            raw: &contents[start..],
+            fields,
+        });
+    }
+
+    Ok(records)
+}
+
+// 第一条记录是表头；columns 为空时在所有列中匹配，但仍然逐列调用 is_match
+pub fn search_records<'a>(
+    contents: &'a str,
+    delimiter: char,
+    columns: &[String],
+    is_match: impl Fn(&str) -> bool,
+) -> Result<Vec<&'a str>, String> {
+    let mut records = parse_records(contents, delimiter)?.into_iter();
+
+    let header = match records.next() {
+        Some(header) => header,
+        None => return Ok(Vec::new()),
+    };
+
+    let indices = columns
+        .iter()
+        .map(|name| {
+            header
+                .fields
+                .iter()
+                .position(|field| field == name)
+                .ok_or_else(|| format!("no column named {} in header", name))
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+
+    Ok(records
+        .filter(|record| {
+            let matches = |field: &String| is_match(field);
+            if indices.is_empty() {
+                record.fields.iter().any(matches)
+            } else {
+                indices
+                    .iter()
+                    .filter_map(|&i| record.fields.get(i))
+                    .any(matches)
+            }
+        })
+        .map(|record| record.raw)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_fields() {
+        let contents = "id,note\r\n1,\"a, b\"\n2,\"say \"\"hi\"\"\nthere\"\n3,";
+        let records = parse_records(contents, ',').unwrap();
+
+        assert_eq!(4, records.len());
+        assert_eq!(vec!["1", "a, b"], records[1].fields);
+        assert_eq!(vec!["2", "say \"hi\"\nthere"], records[2].fields);
+        assert_eq!("2,\"say \"\"hi\"\"\nthere\"", records[2].raw);
+        assert_eq!(vec!["3", ""], records[3].fields);
+    }
+
+    #[test]
+    fn keeps_quotes_inside_unquoted_fields() {
+        let contents = "item,status\n5\" pipe,ok\nvalve,failed\nhose,failed\n";
+        let records = parse_records(contents, ',').unwrap();
+
+        assert_eq!(4, records.len());
+        assert_eq!(vec!["5\" pipe", "ok"], records[1].fields);
+        assert_eq!(
+            vec!["valve,failed", "hose,failed"],
+            search_records(contents, ',', &[String::from("status")], |f| f == "failed").unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        let contents = "id,note\n1,\"ok\"\n2,\"multi\nline\"\n3,\"stray\n4,late\n";
+
+        assert_eq!(
+            Err(String::from("unterminated quoted field starting on line 5")),
+            parse_records(contents, ',').map(|records| records.len())
+        );
+        assert!(search_records(contents, ',', &[], |_| true).is_err());
+    }
+
+    #[test]
+    fn matches_only_selected_columns() {
+        let contents = "\
+name\tstatus
+failed-job\tok
+nightly\tFailed
+";
+        let status = vec![String::from("status")];
+
+        assert_eq!(
+            vec!["nightly\tFailed"],
+            search_records(contents, '\t', &status, |f| f
+                .to_lowercase()
+                .contains("failed"))
+            .unwrap()
+        );
+        assert_eq!(
+            vec!["failed-job\tok"],
+            search_records(contents, '\t', &[], |f| f.contains("failed")).unwrap()
+        );
+        assert!(search_records(contents, '\t', &[String::from("nope")], |_| true).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+// 收集要搜索的文件：目录递归展开，并按需排序
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortBy {
+    // 保持命令行参数的顺序，目录内按文件名遍历
+    #[default]
+    None,
+    Path,
+    // 按最后修改时间从旧到新
+    Modified,
+}
+
+impl SortBy {
+    pub fn parse(value: &str) -> Result<SortBy, &'static str> {
+        match value {
+            "none" => Ok(SortBy::None),
+            "path" => Ok(SortBy::Path),
+            "modified" => Ok(SortBy::Modified),
+            _ => Err("--sort must be one of: path, modified, none"),
+        }
+    }
+}
+
+pub fn collect_files(paths: &[String], sort: SortBy) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        walk(Path::new(path), &mut files)?;
+    }
+
+    match sort {
+        SortBy::None => (),
+        SortBy::Path => files.sort(),
+        SortBy::Modified => {
+            // 先取出修改时间再排序，避免比较时重复读取元数据
+            let mut keyed = files
+                .into_iter()
+                .map(|path| Ok((fs::metadata(&path)?.modified()?, path)))
+                .collect::<io::Result<Vec<(SystemTime, PathBuf)>>>()?;
+            keyed.sort();
+            files = keyed.into_iter().map(|(_, path)| path).collect();
+        }
+    }
+
+    Ok(files)
+}
+
+fn walk(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    // 与 grep -r 一样不跟随遍历中遇到的目录符号链接，避免指向上级目录的链接造成循环；
+    // 命令行上直接给出的路径仍然跟随。
+    // read_dir 的顺序随文件系统而变，按文件名排序后输出顺序才是确定的
+    let mut entries = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk(&path, files)?;
+        } else if !file_type.is_symlink() || !path.is_dir() {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
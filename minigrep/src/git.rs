@@ -0,0 +1,742 @@
+// 直接读取本地 git 仓库的对象库（松散对象和 packfile），不调用 git 命令，也不访问网络。
+// 只支持 SHA-1 仓库和 v2 格式的 pack 索引
+
+use flate2::read::ZlibDecoder;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+pub type Oid = [u8; 20];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+pub struct Commit {
+    pub tree: Oid,
+    pub parents: Vec<Oid>,
+    // 提交者时间戳（Unix 秒），用于 --all-revs 的排序
+    pub time: i64,
+}
+
+pub struct Repo {
+    // 链接的工作树（git worktree add）有自己的 git_dir，只存放 HEAD 等每个工作树独有的文件；
+    // 对象、refs/ 和 packed-refs 在 commondir 指向的主仓库目录中。普通仓库两者相同
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+    work_dir: PathBuf,
+    packs: Vec<Pack>,
+}
+
+struct Pack {
+    index: Vec<u8>,
+    data: Vec<u8>,
+}
+
+fn corrupt(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("corrupt git {}", what))
+}
+
+pub fn to_hex(oid: &Oid) -> String {
+    oid.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Oid> {
+    if hex.len() != 40 {
+        return None;
+    }
+    let mut oid = [0; 20];
+    for (i, byte) in oid.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(oid)
+}
+
+fn inflate(data: &[u8], size_hint: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(size_hint);
+    ZlibDecoder::new(data).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+impl Pack {
+    fn open(index_path: &Path) -> io::Result<Pack> {
+        let index = fs::read(index_path)?;
+        if index.get(..8) != Some(&[0xff, b't', b'O', b'c', 0, 0, 0, 2]) {
+            return Err(corrupt("pack index (only version 2 is supported)"));
+        }
+        if index.len() < 8 + 256 * 4 {
+            return Err(corrupt("pack index"));
+        }
+        let data = fs::read(index_path.with_extension("pack"))?;
+        let pack = Pack { index, data };
+
+        // fanout 表必须单调不减，对象 ID、CRC 和偏移量三张表以及末尾的两个校验和都要在文件之内
+        let mut previous = 0;
+        for byte in 0..256 {
+            let count = pack.fanout(byte)?;
+            if count < previous {
+                return Err(corrupt("pack index fanout"));
+            }
+            previous = count;
+        }
+        let tables = pack
+            .count()?
+            .checked_mul(28)
+            .and_then(|size| size.checked_add(8 + 256 * 4 + 40));
+        if tables.is_none_or(|size| size > pack.index.len()) {
+            return Err(corrupt("pack index"));
+        }
+
+        Ok(pack)
+    }
+
+    // 索引中的偏移量来自文件本身，越界时返回错误而不是 panic
+    fn bytes_at(&self, pos: usize, len: usize) -> io::Result<&[u8]> {
+        pos.checked_add(len)
+            .and_then(|end| self.index.get(pos..end))
+            .ok_or_else(|| corrupt("pack index"))
+    }
+
+    fn u32_at(&self, pos: usize) -> io::Result<usize> {
+        Ok(u32::from_be_bytes(self.bytes_at(pos, 4)?.try_into().unwrap()) as usize)
+    }
+
+    // fanout 表：第 i 项是首字节不大于 i 的对象个数
+    fn fanout(&self, byte: usize) -> io::Result<usize> {
+        self.u32_at(8 + byte * 4)
+    }
+
+    fn count(&self) -> io::Result<usize> {
+        self.fanout(255)
+    }
+
+    fn oid_at(&self, i: usize) -> io::Result<&[u8]> {
+        self.bytes_at(8 + 256 * 4 + i * 20, 20)
+    }
+
+    fn offset_at(&self, i: usize) -> io::Result<usize> {
+        let n = self.count()?;
+        let offset = self.u32_at(8 + 256 * 4 + n * 24 + i * 4)?;
+        if offset & 0x8000_0000 == 0 {
+            return Ok(offset);
+        }
+        // 最高位为 1 时指向 8 字节的大偏移量表
+        let pos = 8 + 256 * 4 + n * 28 + (offset & 0x7fff_ffff) * 8;
+        Ok(u64::from_be_bytes(self.bytes_at(pos, 8)?.try_into().unwrap()) as usize)
+    }
+
+    fn find(&self, oid: &Oid) -> io::Result<Option<usize>> {
+        let first = oid[0] as usize;
+        let mut lo = if first == 0 {
+            0
+        } else {
+            self.fanout(first - 1)?
+        };
+        let mut hi = self.fanout(first)?;
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.oid_at(mid)?.cmp(&oid[..]) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return self.offset_at(mid).map(Some),
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn ids(&self) -> io::Result<Vec<Oid>> {
+        (0..self.count()?)
+            .map(|i| Ok(self.oid_at(i)?.try_into().unwrap()))
+            .collect()
+    }
+}
+
+impl Repo {
+    // 从 start 开始向上查找 .git 目录（或指向 gitdir 的 .git 文件）
+    pub fn discover(start: &Path) -> io::Result<Repo> {
+        for dir in start.ancestors() {
+            let dot_git = dir.join(".git");
+            if dot_git.is_dir() {
+                return Repo::open(dot_git, dir.to_path_buf());
+            }
+            if dot_git.is_file() {
+                let contents = fs::read_to_string(&dot_git)?;
+                let git_dir = contents
+                    .trim()
+                    .strip_prefix("gitdir: ")
+                    .ok_or_else(|| corrupt(".git file"))?;
+                return Repo::open(dir.join(git_dir), dir.to_path_buf());
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "not inside a git repository",
+        ))
+    }
+
+    fn open(git_dir: PathBuf, work_dir: PathBuf) -> io::Result<Repo> {
+        // commondir 的内容通常是相对于 git_dir 的路径
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(contents) => git_dir.join(contents.trim()),
+            Err(_) => git_dir.clone(),
+        };
+
+        let mut packs = Vec::new();
+        if let Ok(entries) = fs::read_dir(common_dir.join("objects/pack")) {
+            for entry in entries {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "idx") {
+                    packs.push(Pack::open(&path)?);
+                }
+            }
+        }
+
+        Ok(Repo {
+            git_dir,
+            common_dir,
+            work_dir,
+            packs,
+        })
+    }
+
+    pub fn read_object(&self, oid: &Oid) -> io::Result<(Kind, Vec<u8>)> {
+        for pack in &self.packs {
+            if let Some(offset) = pack.find(oid)? {
+                return self.read_packed(pack, offset);
+            }
+        }
+
+        self.read_loose(oid)
+    }
+
+    fn read_loose(&self, oid: &Oid) -> io::Result<(Kind, Vec<u8>)> {
+        let hex = to_hex(oid);
+        let path = self
+            .common_dir
+            .join("objects")
+            .join(&hex[..2])
+            .join(&hex[2..]);
+        let compressed = fs::read(&path).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("git object {} not found", hex),
+            ),
+            _ => err,
+        })?;
+
+        let raw = inflate(&compressed, compressed.len() * 2)?;
+        let nul = raw
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| corrupt("loose object"))?;
+        let header = std::str::from_utf8(&raw[..nul]).map_err(|_| corrupt("loose object"))?;
+        let kind = match header.split(' ').next() {
+            Some("commit") => Kind::Commit,
+            Some("tree") => Kind::Tree,
+            Some("blob") => Kind::Blob,
+            Some("tag") => Kind::Tag,
+            _ => return Err(corrupt("loose object type")),
+        };
+
+        Ok((kind, raw[nul + 1..].to_vec()))
+    }
+
+    fn read_packed(&self, pack: &Pack, offset: usize) -> io::Result<(Kind, Vec<u8>)> {
+        let data = &pack.data;
+        let byte = |pos: usize| data.get(pos).copied().ok_or_else(|| corrupt("pack entry"));
+
+        // 类型与大小的变长编码
+        let mut pos = offset;
+        let mut c = byte(pos)?;
+        let kind = (c >> 4) & 7;
+        let mut size = (c & 15) as usize;
+        let mut shift = 4;
+        while c & 0x80 != 0 {
+            pos += 1;
+            c = byte(pos)?;
+            size |= ((c & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+        pos += 1;
+
+        let kind = match kind {
+            1 => Kind::Commit,
+            2 => Kind::Tree,
+            3 => Kind::Blob,
+            4 => Kind::Tag,
+            // OFS_DELTA：基础对象在同一个 pack 中，以相对偏移量表示
+            6 => {
+                let mut c = byte(pos)?;
+                let mut distance = (c & 0x7f) as usize;
+                while c & 0x80 != 0 {
+                    pos += 1;
+                    c = byte(pos)?;
+                    distance = ((distance + 1) << 7) | (c & 0x7f) as usize;
+                }
+                pos += 1;
+                let base_offset = offset
+                    .checked_sub(distance)
+                    .ok_or_else(|| corrupt("delta offset"))?;
+                let (kind, base) = self.read_packed(pack, base_offset)?;
+                let delta = inflate(&data[pos..], size)?;
+                return Ok((kind, apply_delta(&base, &delta)?));
+            }
+            // REF_DELTA：基础对象以对象 ID 表示，可能在别的 pack 中
+            7 => {
+                let base_oid: Oid = data
+                    .get(pos..pos + 20)
+                    .ok_or_else(|| corrupt("pack entry"))?
+                    .try_into()
+                    .unwrap();
+                let (kind, base) = self.read_object(&base_oid)?;
+                let delta = inflate(&data[pos + 20..], size)?;
+                return Ok((kind, apply_delta(&base, &delta)?));
+            }
+            _ => return Err(corrupt("pack entry type")),
+        };
+
+        Ok((kind, inflate(&data[pos..], size)?))
+    }
+
+    // 所有以 prefix（十六进制）开头的对象 ID，用于缩写形式的提交 ID
+    fn find_prefix(&self, prefix: &str) -> io::Result<Vec<Oid>> {
+        let mut found = HashSet::new();
+        for pack in &self.packs {
+            found.extend(
+                pack.ids()?
+                    .into_iter()
+                    .filter(|oid| to_hex(oid).starts_with(prefix)),
+            );
+        }
+
+        let dir = self.common_dir.join("objects").join(&prefix[..2]);
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries {
+                let name = format!("{}{}", &prefix[..2], entry?.file_name().to_string_lossy());
+                if name.starts_with(prefix) {
+                    found.extend(from_hex(&name));
+                }
+            }
+        }
+
+        Ok(found.into_iter().collect())
+    }
+
+    // 解析引用名，先找松散的引用文件，再找 packed-refs
+    fn resolve_ref(&self, name: &str, depth: usize) -> io::Result<Option<Oid>> {
+        if depth > 8 {
+            return Err(corrupt("symbolic ref chain"));
+        }
+        // 引用名（包括符号引用的目标）只能是 git 目录下的相对路径，
+        // 不允许用绝对路径或 .. 读到仓库之外的文件
+        if !Path::new(name)
+            .components()
+            .all(|part| matches!(part, Component::Normal(_)))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid ref name {}", name),
+            ));
+        }
+
+        // 先找工作树自己的引用（HEAD 等），再找共享的引用
+        let contents = fs::read_to_string(self.git_dir.join(name))
+            .or_else(|_| fs::read_to_string(self.common_dir.join(name)));
+        if let Ok(contents) = contents {
+            let contents = contents.trim();
+            return match contents.strip_prefix("ref: ") {
+                Some(target) => self.resolve_ref(target, depth + 1),
+                None => Ok(from_hex(contents)),
+            };
+        }
+
+        Ok(self
+            .packed_refs()
+            .into_iter()
+            .find(|(refname, _)| refname == name)
+            .map(|(_, oid)| oid))
+    }
+
+    fn packed_refs(&self) -> Vec<(String, Oid)> {
+        let contents = fs::read_to_string(self.common_dir.join("packed-refs")).unwrap_or_default();
+        contents
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .filter_map(|line| {
+                let (hex, name) = line.split_once(' ')?;
+                Some((name.to_string(), from_hex(hex)?))
+            })
+            .collect()
+    }
+
+    // 所有引用（HEAD、refs/ 下的松散引用和 packed-refs）指向的对象
+    fn ref_tips(&self) -> io::Result<Vec<Oid>> {
+        let mut tips: Vec<Oid> = self.resolve_ref("HEAD", 0)?.into_iter().collect();
+        tips.extend(self.packed_refs().into_iter().map(|(_, oid)| oid));
+
+        let mut dirs = vec![self.common_dir.join("refs")];
+        while let Some(dir) = dirs.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if let Ok(contents) = fs::read_to_string(&path) {
+                    tips.extend(from_hex(contents.trim()));
+                }
+            }
+        }
+
+        Ok(tips)
+    }
+
+    // 标签对象一直解引用到它指向的提交
+    fn peel_to_commit(&self, mut oid: Oid) -> io::Result<Option<Oid>> {
+        loop {
+            let (kind, data) = self.read_object(&oid)?;
+            match kind {
+                Kind::Commit => return Ok(Some(oid)),
+                Kind::Tag => {
+                    let text = String::from_utf8_lossy(&data);
+                    oid = text
+                        .lines()
+                        .find_map(|line| line.strip_prefix("object "))
+                        .and_then(from_hex)
+                        .ok_or_else(|| corrupt("tag"))?;
+                }
+                _ => return Ok(None),
+            }
+        }
+    }
+
+    // 支持完整或缩写的提交 ID、HEAD、分支和标签名，以及 ~N、^N 后缀
+    pub fn resolve(&self, rev: &str) -> io::Result<Oid> {
+        let unknown =
+            || io::Error::new(io::ErrorKind::NotFound, format!("unknown revision {}", rev));
+
+        let split = rev.find(['~', '^']).unwrap_or(rev.len());
+        let (base, mut suffix) = rev.split_at(split);
+
+        let mut oid = None;
+        for name in [
+            base.to_string(),
+            format!("refs/{}", base),
+            format!("refs/tags/{}", base),
+            format!("refs/heads/{}", base),
+            format!("refs/remotes/{}", base),
+            format!("refs/remotes/{}/HEAD", base),
+        ] {
+            if let Some(found) = self.resolve_ref(&name, 0)? {
+                oid = Some(found);
+                break;
+            }
+        }
+        if oid.is_none() && base.len() >= 4 && base.chars().all(|c| c.is_ascii_hexdigit()) {
+            let matches = self.find_prefix(&base.to_ascii_lowercase())?;
+            if matches.len() > 1 {
+                return Err(io::Error::other(format!("ambiguous revision {}", base)));
+            }
+            oid = matches.into_iter().next();
+        }
+
+        let mut commit = self
+            .peel_to_commit(oid.ok_or_else(unknown)?)?
+            .ok_or_else(unknown)?;
+
+        while let Some(op) = suffix.chars().next() {
+            let digits = suffix[1..]
+                .find(|c: char| !c.is_ascii_digit())
+                .map_or(suffix.len(), |i| i + 1);
+            let n: usize = match &suffix[1..digits] {
+                "" => 1,
+                number => number.parse().map_err(|_| unknown())?,
+            };
+            suffix = &suffix[digits..];
+
+            if op == '~' {
+                for _ in 0..n {
+                    commit = *self.commit(&commit)?.parents.first().ok_or_else(unknown)?;
+                }
+            } else if n > 0 {
+                commit = *self
+                    .commit(&commit)?
+                    .parents
+                    .get(n - 1)
+                    .ok_or_else(unknown)?;
+            }
+        }
+
+        Ok(commit)
+    }
+
+    pub fn commit(&self, oid: &Oid) -> io::Result<Commit> {
+        let (kind, data) = self.read_object(oid)?;
+        if kind != Kind::Commit {
+            return Err(corrupt("commit"));
+        }
+
+        let text = String::from_utf8_lossy(&data);
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut time = 0;
+        // 头部在第一个空行之前
+        for line in text.lines().take_while(|line| !line.is_empty()) {
+            if let Some(hex) = line.strip_prefix("tree ") {
+                tree = from_hex(hex);
+            } else if let Some(hex) = line.strip_prefix("parent ") {
+                parents.extend(from_hex(hex));
+            } else if let Some(committer) = line.strip_prefix("committer ") {
+                time = committer
+                    .rsplit(' ')
+                    .nth(1)
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or(0);
+            }
+        }
+
+        Ok(Commit {
+            tree: tree.ok_or_else(|| corrupt("commit"))?,
+            parents,
+            time,
+        })
+    }
+
+    // 从所有引用可达的提交，按提交时间从新到旧排列。
+    // 浅克隆（git clone --depth）的 shallow 文件列出的提交没有父提交可读，历史到此为止
+    pub fn all_commits(&self) -> io::Result<Vec<Oid>> {
+        let shallow: HashSet<Oid> = fs::read_to_string(self.common_dir.join("shallow"))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| from_hex(line.trim()))
+            .collect();
+
+        let mut commits = HashMap::new();
+        let mut queue = VecDeque::new();
+        for tip in self.ref_tips()? {
+            queue.extend(self.peel_to_commit(tip)?);
+        }
+
+        while let Some(oid) = queue.pop_front() {
+            if commits.contains_key(&oid) {
+                continue;
+            }
+            let commit = self.commit(&oid)?;
+            if !shallow.contains(&oid) {
+                queue.extend(commit.parents.iter().copied());
+            }
+            commits.insert(oid, commit.time);
+        }
+
+        let mut commits: Vec<(i64, Oid)> = commits.into_iter().map(|(oid, t)| (t, oid)).collect();
+        commits.sort_by(|a, b| b.cmp(a));
+        Ok(commits.into_iter().map(|(_, oid)| oid).collect())
+    }
+
+    // 递归列出树中的所有文件（路径, blob ID），跳过子模块
+    pub fn tree_files(&self, tree: &Oid) -> io::Result<Vec<(String, Oid)>> {
+        let mut files = Vec::new();
+        self.walk_tree(tree, "", &mut files)?;
+        Ok(files)
+    }
+
+    fn walk_tree(
+        &self,
+        tree: &Oid,
+        prefix: &str,
+        files: &mut Vec<(String, Oid)>,
+    ) -> io::Result<()> {
+        let (kind, data) = self.read_object(tree)?;
+        if kind != Kind::Tree {
+            return Err(corrupt("tree"));
+        }
+
+        // 每一项是 "<mode> <name>\0<20 字节 ID>"
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            let space = rest
+                .iter()
+                .position(|&b| b == b' ')
+                .ok_or_else(|| corrupt("tree"))?;
+            let nul = rest
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or_else(|| corrupt("tree"))?;
+            let oid: Oid = rest
+                .get(nul + 1..nul + 21)
+                .ok_or_else(|| corrupt("tree"))?
+                .try_into()
+                .unwrap();
+            let mode = &rest[..space];
+            let name = String::from_utf8_lossy(&rest[space + 1..nul]);
+            let path = if prefix.is_empty() {
+                name.into_owned()
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+
+            match mode {
+                b"40000" => self.walk_tree(&oid, &path, files)?,
+                b"160000" => (),
+                _ => files.push((path, oid)),
+            }
+            rest = &rest[nul + 21..];
+        }
+
+        Ok(())
+    }
+
+    // 把相对于 cwd 的路径转换为相对于工作区根目录的路径，用于过滤树中的文件
+    pub fn pathspec(&self, cwd: &Path, path: &str) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        let relative = cwd.strip_prefix(&self.work_dir).unwrap_or(Path::new(""));
+        for component in relative.join(path).components() {
+            match component {
+                Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+                Component::ParentDir => {
+                    parts.pop();
+                }
+                _ => (),
+            }
+        }
+        parts.join("/")
+    }
+}
+
+// 空的 pathspec 表示整个仓库
+pub fn matches_pathspec(path: &str, specs: &[String]) -> bool {
+    specs.iter().any(|spec| {
+        spec.is_empty()
+            || path == spec
+            || (path.starts_with(spec.as_str()) && path.as_bytes().get(spec.len()) == Some(&b'/'))
+    })
+}
+
+// 按 git 的 delta 格式，用 base 和指令序列重建对象
+fn apply_delta(base: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+    let mut pos = 0;
+    let mut varint = || {
+        let mut value = 0usize;
+        let mut shift = 0;
+        loop {
+            let c = *delta.get(pos).ok_or_else(|| corrupt("delta"))?;
+            pos += 1;
+            value |= ((c & 0x7f) as usize) << shift;
+            shift += 7;
+            if c & 0x80 == 0 {
+                return Ok::<usize, io::Error>(value);
+            }
+        }
+    };
+
+    let base_size = varint()?;
+    let result_size = varint()?;
+    if base_size != base.len() {
+        return Err(corrupt("delta base"));
+    }
+
+    let mut out = Vec::with_capacity(result_size);
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+
+        if op & 0x80 != 0 {
+            // 从 base 复制：低 4 位表示偏移量的字节，接下来 3 位表示长度的字节
+            let mut offset = 0usize;
+            let mut size = 0usize;
+            for i in 0..4 {
+                if op & (1 << i) != 0 {
+                    offset |=
+                        (*delta.get(pos).ok_or_else(|| corrupt("delta"))? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            for i in 0..3 {
+                if op & (0x10 << i) != 0 {
+                    size |= (*delta.get(pos).ok_or_else(|| corrupt("delta"))? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            out.extend_from_slice(
+                base.get(offset..offset + size)
+                    .ok_or_else(|| corrupt("delta"))?,
+            );
+        } else if op != 0 {
+            // 插入 delta 中接下来的 op 个字节
+            let len = op as usize;
+            out.extend_from_slice(delta.get(pos..pos + len).ok_or_else(|| corrupt("delta"))?);
+            pos += len;
+        } else {
+            return Err(corrupt("delta"));
+        }
+    }
+
+    if out.len() != result_size {
+        return Err(corrupt("delta result"));
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_truncated_pack_indexes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("pack-test.idx");
+        fs::write(tmp.path().join("pack-test.pack"), b"PACK").unwrap();
+
+        // fanout 表声称有 1000 个对象，但文件到 fanout 表为止
+        let mut index = vec![0xff, b't', b'O', b'c', 0, 0, 0, 2];
+        for _ in 0..256 {
+            index.extend_from_slice(&1000u32.to_be_bytes());
+        }
+        fs::write(&path, &index).unwrap();
+        assert!(Pack::open(&path).is_err());
+
+        // fanout 表递减
+        index[8..12].copy_from_slice(&2000u32.to_be_bytes());
+        fs::write(&path, &index).unwrap();
+        assert!(Pack::open(&path).is_err());
+    }
+
+    #[test]
+    fn applies_delta() {
+        let base = b"hello, world";
+        // 大小 12 -> 14；复制 base[0..7]，插入 "rust", 复制 base[7..10]
+        let delta = [12, 14, 0x91, 0, 7, 4, b'r', b'u', b's', b't', 0x91, 7, 3];
+
+        assert_eq!(
+            b"hello, rustwor".to_vec(),
+            apply_delta(base, &delta).unwrap()
+        );
+        assert!(apply_delta(b"short", &delta).is_err());
+    }
+
+    #[test]
+    fn filters_pathspecs() {
+        let specs = vec![String::from("src"), String::from("README.md")];
+
+        assert!(matches_pathspec("src/lib.rs", &specs));
+        assert!(matches_pathspec("README.md", &specs));
+        assert!(!matches_pathspec("srcs/lib.rs", &specs));
+        assert!(matches_pathspec("anything", &[String::new()]));
+    }
+}
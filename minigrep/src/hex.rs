@@ -0,0 +1,148 @@
+// --bytes：查询写成十六进制字节（?? 为通配符），在原始字节上搜索
+
+use memchr::memchr_iter;
+use std::fmt::Write;
+
+// 每行十六进制转储显示的字节数
+const ROW: usize = 16;
+
+// None 表示 ?? 通配符
+pub type BytePattern = Vec<Option<u8>>;
+
+// 空白只用于分隔，"DE AD ?? EF" 与 "DEAD??EF" 等价
+pub fn parse_pattern(query: &str) -> Result<BytePattern, &'static str> {
+    let digits: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err("--bytes query must be pairs of hex digits or ??");
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| match pair {
+            ['?', '?'] => Ok(None),
+            [high, low] => match (high.to_digit(16), low.to_digit(16)) {
+                (Some(high), Some(low)) => Ok(Some((high * 16 + low) as u8)),
+                _ => Err("--bytes query must be pairs of hex digits or ??"),
+            },
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+// 返回所有（不重叠的）匹配的起始偏移量
+pub fn find_pattern(haystack: &[u8], pattern: &[Option<u8>]) -> Vec<usize> {
+    let matches_at = |start: usize| {
+        haystack[start..start + pattern.len()]
+            .iter()
+            .zip(pattern)
+            .all(|(byte, expected)| expected.is_none_or(|e| e == *byte))
+    };
+
+    let mut offsets = Vec::new();
+    if pattern.is_empty() || pattern.len() > haystack.len() {
+        return offsets;
+    }
+    let last = haystack.len() - pattern.len();
+
+    // 以第一个确定的字节为锚点，用 memchr 跳过不可能匹配的位置
+    let anchor = match pattern.iter().position(Option::is_some) {
+        Some(anchor) => anchor,
+        None => {
+            offsets.extend((0..=last).step_by(pattern.len()));
+            return offsets;
+        }
+    };
+    let anchor_byte = pattern[anchor].unwrap();
+
+    let mut next = 0;
+    for i in memchr_iter(anchor_byte, &haystack[anchor..]) {
+        let start = i;
+        if start > last {
+            break;
+        }
+        if start >= next && matches_at(start) {
+            offsets.push(start);
+            next = start + pattern.len();
+        }
+    }
+
+    offsets
+}
+
+// 按 hexdump -C 的格式转储 [start, end) 所在的行，前后各多显示一行作为上下文
+pub fn hexdump(bytes: &[u8], start: usize, end: usize) -> String {
+    let first = (start / ROW).saturating_sub(1) * ROW;
+    let last = ((end.div_ceil(ROW) + 1) * ROW).min(bytes.len());
+
+    let mut dump = String::new();
+    for row in (first..last).step_by(ROW) {
+        let chunk = &bytes[row..(row + ROW).min(bytes.len())];
+
+        let _ = write!(dump, "{:08x} ", row);
+        for i in 0..ROW {
+            if i == ROW / 2 {
+                dump.push(' ');
+            }
+            match chunk.get(i) {
+                Some(byte) => {
+                    let _ = write!(dump, " {:02x}", byte);
+                }
+                None => dump.push_str("   "),
+            }
+        }
+
+        dump.push_str("  |");
+        dump.extend(chunk.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        dump.push_str("|\n");
+    }
+
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_with_wildcards() {
+        assert_eq!(
+            Ok(vec![Some(0xde), Some(0xad), None, Some(0xef)]),
+            parse_pattern("DE AD ?? ef")
+        );
+        assert_eq!(parse_pattern("DE AD ?? ef"), parse_pattern("DEAD??EF"));
+        assert!(parse_pattern("DEA").is_err());
+        assert!(parse_pattern("ZZ").is_err());
+        assert!(parse_pattern("").is_err());
+    }
+
+    #[test]
+    fn finds_patterns() {
+        let haystack = b"\x00\xde\xad\x01\xef\xde\xad\xbe\xef\xde";
+        let pattern = parse_pattern("DE AD ?? EF").unwrap();
+
+        assert_eq!(vec![1, 5], find_pattern(haystack, &pattern));
+        assert_eq!(
+            vec![0, 2, 4, 6],
+            find_pattern(&haystack[..9], &[None, None])
+        );
+        assert_eq!(Vec::<usize>::new(), find_pattern(b"\xde", &pattern));
+    }
+
+    #[test]
+    fn dumps_surrounding_rows() {
+        let bytes: Vec<u8> = (0..64).collect();
+
+        let dump = hexdump(&bytes, 34, 36);
+        let rows: Vec<&str> = dump.lines().collect();
+        assert_eq!(3, rows.len());
+        assert!(rows[0].starts_with("00000010  10 11"));
+        assert!(rows[2].starts_with("00000030  30 31"));
+        assert!(rows[1].ends_with("| !\"#$%&'()*+,-./|"));
+    }
+}
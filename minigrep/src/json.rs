@@ -0,0 +1,118 @@
+// JSON Lines 的结构化搜索：按 --json-path 取出字段值，只在该值上匹配查询
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+// 支持 $.a.b、$['a b'] 与 $.list[0] 这几种写法
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<JsonPath, &'static str> {
+        let mut rest = path
+            .strip_prefix('$')
+            .ok_or("--json-path must start with $")?;
+        let mut segments = Vec::new();
+
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return Err("--json-path has an empty key");
+                }
+                segments.push(Segment::Key(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or("--json-path has an unclosed [")?;
+                let inner = &after[..end];
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+                let segment = match quoted {
+                    Some(key) => Segment::Key(key.to_string()),
+                    None => Segment::Index(
+                        inner
+                            .parse()
+                            .map_err(|_| "--json-path index must be a number")?,
+                    ),
+                };
+                segments.push(segment);
+                rest = &after[end + 1..];
+            } else {
+                return Err("--json-path segments must start with . or [");
+            }
+        }
+
+        Ok(JsonPath { segments })
+    }
+
+    pub fn select<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        self.segments
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                Segment::Key(key) => value.get(key.as_str()),
+                Segment::Index(index) => value.get(*index),
+            })
+    }
+}
+
+// 无法解析为 JSON 或缺少该字段的行不算匹配。
+// 字符串值按原文交给 is_match，其余类型按 JSON 文本匹配
+pub fn search_json<'a>(
+    contents: &'a str,
+    path: &JsonPath,
+    is_match: impl Fn(&str) -> bool,
+) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| {
+            let value: Value = match serde_json::from_str(line) {
+                Ok(value) => value,
+                Err(_) => return false,
+            };
+            match path.select(&value) {
+                Some(Value::String(text)) => is_match(text),
+                Some(other) => is_match(&other.to_string()),
+                None => false,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_paths() {
+        let path = JsonPath::parse("$.req['user agent'].tags[1]").unwrap();
+        let value: Value =
+            serde_json::from_str(r#"{"req":{"user agent":{"tags":["a","b"]}}}"#).unwrap();
+
+        assert_eq!(Some(&Value::from("b")), path.select(&value));
+        assert!(JsonPath::parse("level").is_err());
+        assert!(JsonPath::parse("$.a[x]").is_err());
+    }
+
+    #[test]
+    fn matches_selected_field_only() {
+        let path = JsonPath::parse("$.level").unwrap();
+        let contents = r#"{"level":"info","msg":"error budget ok"}
+{"level":"ERROR","msg":"disk full"}
+not json at all error
+{"msg":"error without level"}"#;
+
+        assert_eq!(
+            vec![r#"{"level":"ERROR","msg":"disk full"}"#],
+            search_json(contents, &path, |v| v.to_lowercase().contains("error"))
+        );
+    }
+}
@@ -1,9 +1,64 @@
-use std::{env, error::Error, fs};
+use memchr::{memchr, memchr2, memmem, memrchr};
+use std::{collections::HashSet, env, error::Error, fs, path::Path, time::Instant};
 
+mod archive;
+mod binary;
+pub mod cli;
+mod csv;
+mod files;
+pub mod git;
+mod hex;
+mod json;
+mod pre;
+mod query;
+mod record;
+mod stats;
+mod timerange;
+
+pub use archive::{is_archive, members, Member};
+pub use binary::{is_binary, BinaryFiles};
+pub use csv::{parse_records, search_records, Record};
+pub use files::{collect_files, SortBy};
+pub use hex::{find_pattern, hexdump, parse_pattern, BytePattern};
+pub use json::{search_json, JsonPath};
+pub use pre::{glob_match, preprocess, should_preprocess};
+pub use query::Expr;
+pub use record::{paragraphs, records_starting_at, RecordSeparator};
+pub use stats::{count_lines, Stats};
+pub use timerange::{parse_timestamp, TimeRange};
+
+#[derive(Default)]
 pub struct Config {
     pub query: String,
-    pub file_path: String,
+    // --bool 时把查询解析为布尔表达式
+    pub expr: Option<Expr>,
+    // 按记录而不是按行匹配，--paragraph 等同于以空行分隔
+    pub record_separator: Option<RecordSeparator>,
+    pub file_paths: Vec<String>,
     pub case_sensitive: bool,
+    pub binary_files: BinaryFiles,
+    pub sort: SortBy,
+    pub unique: bool,
+    pub only_matching: bool,
+    // --csv / --tsv 时的字段分隔符
+    pub delimiter: Option<char>,
+    pub fields: Vec<String>,
+    // JSON Lines 输入时只在该字段上匹配
+    pub json_path: Option<JsonPath>,
+    // --since / --until 限定的时间窗口
+    pub time_range: Option<TimeRange>,
+    // --pre 指定的预处理命令，以及限定预处理范围的 --pre-glob
+    pub pre: Option<String>,
+    pub pre_globs: Vec<String>,
+    // 结束后打印统计信息
+    pub stats: bool,
+    // --bytes 时查询被解析为十六进制字节模式，在原始字节上搜索
+    pub byte_pattern: Option<BytePattern>,
+    // 在 git 历史中搜索：--rev 指定的版本，或 --all-revs 的所有提交
+    pub rev: Option<String>,
+    pub all_revs: bool,
+    // 设置时不搜索，只输出补全脚本或 man 手册
+    pub generate: Option<Generate>,
 }
 
 // impl Config {
@@ -44,48 +99,485 @@ pub struct Config {
 
 impl Config {
     // 返回 Result 来替代直接 panic
-    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
+    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
         // 使用迭代器解析参数
         // 第一个参数是程序名，由于无需使用，因此这里直接空调用一次
         args.next();
 
-        let query = match args.next() {
+        // 按 cli::FLAGS 中的声明识别选项，其余参数按顺序作为位置参数
+        let matches = cli::parse(args)?;
+        let mut positional = matches.positional.clone().into_iter();
+
+        // --generate 不需要查询和文件
+        if let Some(kind) = matches.value("generate") {
+            let generate = match kind {
+                "man" => Generate::Man,
+                "completions" => Generate::Completions(
+                    positional
+                        .next()
+                        .ok_or("--generate completions needs a shell: bash, zsh or fish")?,
+                ),
+                _ => return Err("--generate must be one of: completions, man".into()),
+            };
+            return Ok(Config {
+                generate: Some(generate),
+                ..Config::default()
+            });
+        }
+
+        let query = match positional.next() {
             Some(arg) => arg,
-            None => return Err("Didn't get query string"),
+            None => return Err("Didn't get query string".into()),
         };
 
-        let file_path = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get file path string"),
+        let rev = matches.value("rev").map(String::from);
+        let all_revs = matches.flag("all-revs");
+        if rev.is_some() && all_revs {
+            return Err("--rev cannot be combined with --all-revs".into());
+        }
+
+        // 剩余的位置参数都是要搜索的文件或目录；在 git 历史中搜索时默认为整个仓库
+        let mut file_paths: Vec<String> = positional.collect();
+        if file_paths.is_empty() {
+            if rev.is_none() && !all_revs {
+                return Err("Didn't get file path string".into());
+            }
+            file_paths.push(String::from("."));
+        }
+
+        let binary_files = match matches.value("binary-files") {
+            Some(value) => BinaryFiles::parse(value)?,
+            None => BinaryFiles::default(),
+        };
+        let sort = match matches.value("sort") {
+            Some(value) => SortBy::parse(value)?,
+            None => SortBy::default(),
+        };
+        let unique = matches.flag("unique");
+        let only_matching = matches.flag("only-matching");
+
+        let delimiter = if matches.flag("csv") {
+            Some(',')
+        } else if matches.flag("tsv") {
+            Some('\t')
+        } else {
+            None
+        };
+        let fields = matches.values("field");
+        let json_path = match matches.value("json-path") {
+            Some(value) => Some(JsonPath::parse(value)?),
+            None => None,
+        };
+
+        let pre = matches.value("pre").map(String::from);
+        let pre_globs = matches.values("pre-glob");
+
+        let record_separator = match matches.value("record-separator") {
+            Some(value) => Some(RecordSeparator::parse(value)?),
+            None if matches.flag("paragraph") => Some(RecordSeparator::Blank),
+            None => None,
+        };
+        let stats = matches.flag("stats");
+
+        let expr = if matches.flag("bool") {
+            if only_matching {
+                return Err("--only-matching cannot be combined with --bool".into());
+            }
+            Some(Expr::parse(&query)?)
+        } else {
+            None
+        };
+        if record_separator.is_some() && (delimiter.is_some() || json_path.is_some()) {
+            return Err("record mode cannot be combined with --csv, --tsv or --json-path".into());
+        }
+        if pre.is_some() && (rev.is_some() || all_revs) {
+            return Err("--pre cannot be combined with --rev or --all-revs".into());
+        }
+        if !pre_globs.is_empty() && pre.is_none() {
+            return Err("--pre-glob requires --pre".into());
+        }
+        if !fields.is_empty() && delimiter.is_none() {
+            return Err("--field requires --csv or --tsv".into());
+        }
+        if json_path.is_some() && delimiter.is_some() {
+            return Err("--json-path cannot be combined with --csv or --tsv".into());
+        }
+
+        let since = matches.value("since");
+        let until = matches.value("until");
+        let time_range = if since.is_some() || until.is_some() {
+            if delimiter.is_some() {
+                return Err("--since/--until cannot be combined with --csv or --tsv".into());
+            }
+            Some(TimeRange::build(
+                since,
+                until,
+                matches.value("time-format").map(String::from),
+                matches.flag("time-sorted"),
+            )?)
+        } else {
+            None
+        };
+
+        let byte_pattern = if matches.flag("bytes") {
+            if expr.is_some()
+                || only_matching
+                || delimiter.is_some()
+                || json_path.is_some()
+                || record_separator.is_some()
+                || time_range.is_some()
+            {
+                return Err("--bytes cannot be combined with text matching options".into());
+            }
+            Some(parse_pattern(&query)?)
+        } else {
+            None
         };
 
         let case_sensitive = env::var("CASE_SENSITIVE").is_ok();
 
         Ok(Config {
             query,
-            file_path,
+            expr,
+            record_separator,
+            file_paths,
             case_sensitive,
+            binary_files,
+            sort,
+            unique,
+            only_matching,
+            delimiter,
+            fields,
+            json_path,
+            time_range,
+            pre,
+            pre_globs,
+            stats,
+            byte_pattern,
+            rev,
+            all_revs,
+            generate: None,
         })
     }
 }
 
+// --generate 要输出的内容，由 FLAGS 表生成
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Generate {
+    Completions(String),
+    Man,
+}
+
+impl Generate {
+    pub fn render(&self) -> Result<String, String> {
+        match self {
+            Generate::Completions(shell) => cli::completions(shell),
+            Generate::Man => Ok(cli::man_page()),
+        }
+    }
+}
+
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    // 读取文件
-    let contents = fs::read_to_string(config.file_path)?;
+    // --unique 跨所有文件去重
+    let mut seen = HashSet::new();
+    let mut stats = Stats::default();
 
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
+    if config.rev.is_some() || config.all_revs {
+        search_revisions(&config, &mut seen, &mut stats)?;
     } else {
-        search_insensitive(&config.query, &contents)
+        let files = collect_files(&config.file_paths, config.sort)?;
+
+        // 多个文件或搜索目录时，在每行前加上文件路径
+        let with_name = files.len() > 1 || config.file_paths.iter().any(|p| Path::new(p).is_dir());
+
+        for path in &files {
+            search_file(&config, path, with_name, &mut seen, &mut stats)?;
+        }
+    }
+
+    if config.stats {
+        println!();
+        println!("{}", stats);
+    }
+
+    Ok(())
+}
+
+fn search_file(
+    config: &Config,
+    path: &Path,
+    with_name: bool,
+    seen: &mut HashSet<String>,
+    stats: &mut Stats,
+) -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+
+    // 读取文件，按字节读取以便检测二进制内容；需要预处理时改为读取命令的输出
+    let (bytes, preprocessed) = match &config.pre {
+        Some(command) if should_preprocess(&config.pre_globs, path) => {
+            (preprocess(command, path)?, true)
+        }
+        _ => (fs::read(path)?, false),
     };
+    let name = path.display().to_string();
 
-    for line in results {
-        println!("{}", line);
+    // 归档中的每个成员作为一个虚拟文件搜索，总是带上 archive!/inner 形式的名字。
+    // 读不了的归档（只是扩展名相同、zip64、不支持的压缩方式等）在标准错误上提示，
+    // 然后按普通文件搜索原始内容，不中断其余文件的搜索
+    if !preprocessed && is_archive(&name) {
+        match members(&name, &bytes) {
+            Ok(members) => {
+                stats.read_time += start.elapsed();
+                // 单个成员读不了（例如解压后过大）时只跳过该成员
+                for member in members {
+                    match &member.data {
+                        Ok(data) => {
+                            search_contents(config, &member.name, data, true, seen, stats)?;
+                        }
+                        Err(e) => eprintln!("minigrep: {}: {}", member.name, e),
+                    }
+                }
+                return Ok(());
+            }
+            Err(e) => eprintln!("minigrep: {}: {}, searching it as a plain file", name, e),
+        }
     }
 
+    stats.read_time += start.elapsed();
+
+    search_contents(config, &name, &bytes, with_name, seen, stats)?;
+
     Ok(())
 }
 
+// 在 git 历史中搜索，输出 rev:path:line。
+// 同一个 blob 会出现在许多提交中，已知不匹配的 blob 不再重复搜索
+fn search_revisions(
+    config: &Config,
+    seen: &mut HashSet<String>,
+    stats: &mut Stats,
+) -> Result<(), Box<dyn Error>> {
+    let cwd = env::current_dir()?;
+    let repo = git::Repo::discover(&cwd)?;
+    let specs: Vec<String> = config
+        .file_paths
+        .iter()
+        .map(|path| repo.pathspec(&cwd, path))
+        .collect();
+
+    let revs: Vec<(String, git::Oid)> = match &config.rev {
+        Some(rev) => vec![(rev.clone(), repo.resolve(rev)?)],
+        None => repo
+            .all_commits()?
+            .into_iter()
+            .map(|oid| (git::to_hex(&oid)[..7].to_string(), oid))
+            .collect(),
+    };
+
+    let mut no_match = HashSet::new();
+    for (label, commit) in revs {
+        let tree = repo.commit(&commit)?.tree;
+        for (path, blob) in repo.tree_files(&tree)? {
+            if !git::matches_pathspec(&path, &specs) || no_match.contains(&blob) {
+                continue;
+            }
+
+            let start = Instant::now();
+            let (_, bytes) = repo.read_object(&blob)?;
+            stats.read_time += start.elapsed();
+
+            let name = format!("{}:{}", label, path);
+            if !search_contents(config, &name, &bytes, true, seen, stats)? {
+                no_match.insert(blob);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// 搜索一段已读入的内容并打印结果，name 是输出中显示的名字。返回是否有匹配
+fn search_contents(
+    config: &Config,
+    name: &str,
+    bytes: &[u8],
+    with_name: bool,
+    seen: &mut HashSet<String>,
+    stats: &mut Stats,
+) -> Result<bool, Box<dyn Error>> {
+    stats.files_searched += 1;
+    stats.bytes_read += bytes.len();
+
+    if let Some(pattern) = &config.byte_pattern {
+        return Ok(search_file_bytes(pattern, bytes, name, with_name, stats));
+    }
+
+    let start = Instant::now();
+
+    let binary = is_binary(bytes);
+    if binary && config.binary_files == BinaryFiles::WithoutMatch {
+        stats.search_time += start.elapsed();
+        return Ok(false);
+    }
+
+    // 非 UTF-8 的内容按有损方式转换，而不是直接报错
+    let contents = String::from_utf8_lossy(bytes);
+
+    let results = find_lines(config, &contents)?;
+
+    // 统计本身有开销，只在需要时计算
+    if config.stats {
+        stats.lines_scanned += count_lines(&contents);
+        stats.matching_lines += results.len();
+        stats.matches += count_matches(config, &results);
+    }
+
+    stats.search_time += start.elapsed();
+    let start = Instant::now();
+
+    // 默认情况下不打印二进制文件的匹配行，只报告是否匹配
+    if binary && config.binary_files == BinaryFiles::Binary {
+        if !results.is_empty() {
+            println!("Binary file {} matches", name);
+        }
+        stats.print_time += start.elapsed();
+        return Ok(!results.is_empty());
+    }
+
+    let matched = !results.is_empty();
+    for line in results {
+        // -o 只打印每一处匹配的文本
+        let outputs = if config.only_matching {
+            find_matches(&config.query, line, config.case_sensitive)
+        } else {
+            vec![line]
+        };
+
+        for output in outputs {
+            if config.unique && !seen.insert(output.to_string()) {
+                continue;
+            }
+
+            if with_name {
+                println!("{}:{}", name, output);
+            } else {
+                println!("{}", output);
+            }
+        }
+    }
+
+    stats.print_time += start.elapsed();
+
+    Ok(matched)
+}
+
+// 按字节搜索：每处匹配打印偏移量，以及所在位置的十六进制转储
+fn search_file_bytes(
+    pattern: &[Option<u8>],
+    bytes: &[u8],
+    name: &str,
+    with_name: bool,
+    stats: &mut Stats,
+) -> bool {
+    let start = Instant::now();
+    let offsets = find_pattern(bytes, pattern);
+    stats.matches += offsets.len();
+    stats.search_time += start.elapsed();
+
+    let start = Instant::now();
+    let matched = !offsets.is_empty();
+    for offset in offsets {
+        if with_name {
+            println!("{}:{:#010x}", name, offset);
+        } else {
+            println!("{:#010x}", offset);
+        }
+        print!("{}", hexdump(bytes, offset, offset + pattern.len()));
+    }
+    stats.print_time += start.elapsed();
+
+    matched
+}
+
+// 普通的字面量查询统计每一处出现；布尔表达式和按字段匹配时，每条结果计为一次
+fn count_matches(config: &Config, results: &[&str]) -> usize {
+    if config.expr.is_some() || config.delimiter.is_some() || config.json_path.is_some() {
+        return results.len();
+    }
+
+    results
+        .iter()
+        .map(|line| find_matches(&config.query, line, config.case_sensitive).len())
+        .sum()
+}
+
+// 根据配置选择搜索方式，返回需要打印的行（或记录）
+fn find_lines<'a>(config: &Config, contents: &'a str) -> Result<Vec<&'a str>, Box<dyn Error>> {
+    // 有时间窗口时只在窗口内的区间上搜索
+    if let Some(range) = &config.time_range {
+        let mut results = Vec::new();
+        for span in range.spans(contents) {
+            results.extend(match_lines(config, &contents[span])?);
+        }
+        return Ok(results);
+    }
+
+    match_lines(config, contents)
+}
+
+fn match_lines<'a>(config: &Config, contents: &'a str) -> Result<Vec<&'a str>, Box<dyn Error>> {
+    let is_match = |text: &str| text_matches(config, text);
+
+    if let Some(delimiter) = config.delimiter {
+        return Ok(search_records(
+            contents,
+            delimiter,
+            &config.fields,
+            is_match,
+        )?);
+    }
+
+    if let Some(path) = &config.json_path {
+        return Ok(search_json(contents, path, is_match));
+    }
+
+    if let Some(separator) = &config.record_separator {
+        return Ok(separator
+            .split(contents)
+            .into_iter()
+            .filter(|record| is_match(record))
+            .collect());
+    }
+
+    // 布尔表达式逐行求值
+    if config.expr.is_some() {
+        return Ok(contents.lines().filter(|line| is_match(line)).collect());
+    }
+
+    Ok(if config.case_sensitive {
+        search(&config.query, contents)
+    } else {
+        search_insensitive(&config.query, contents)
+    })
+}
+
+// 一段文本（行、记录或字段值）是否匹配查询
+fn text_matches(config: &Config, text: &str) -> bool {
+    match &config.expr {
+        Some(expr) => expr.is_match(text, config.case_sensitive),
+        None => contains(&config.query, text, config.case_sensitive),
+    }
+}
+
+// 判断单段文本是否包含查询，供按字段、按记录匹配的模式使用
+pub(crate) fn contains(query: &str, text: &str, case_sensitive: bool) -> bool {
+    if case_sensitive {
+        text.contains(query)
+    } else {
+        text.to_lowercase().contains(&query.to_lowercase())
+    }
+}
+
 // pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
 //     let mut results = Vec::new();
 
@@ -98,20 +590,170 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
 //     results
 // }
 
+// pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+//     contents
+//         .lines()
+//         .filter(|line| line.contains(query))
+//         .collect()
+// }
+
+// 不再逐行调用 contains，而是用向量化的子串查找器扫描整个缓冲区，
+// 只在命中位置附近查找行边界
 pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents
-        .lines()
-        .filter(|line| line.contains(query))
-        .collect()
+    if query.is_empty() {
+        return contents.lines().collect();
+    }
+
+    literal_lines(query.as_bytes(), contents.as_bytes(), contents)
 }
 
 pub fn search_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
     let query = query.to_lowercase();
 
-    contents
-        .lines()
-        .filter(|line| line.to_lowercase().contains(&query))
-        .collect()
+    if query.is_empty() {
+        return contents.lines().collect();
+    }
+
+    // 非 ASCII 查询的小写形式可能改变长度，只能逐行比较
+    if !query.is_ascii() {
+        return contents
+            .lines()
+            .filter(|line| line.to_lowercase().contains(&query))
+            .collect();
+    }
+
+    // ASCII 小写转换不改变字节长度（UTF-8 多字节序列原样保留），
+    // 整个缓冲区只需转换一次，偏移量仍然对应原文
+    let lowered = contents.to_ascii_lowercase();
+    let hits = literal_lines(query.as_bytes(), lowered.as_bytes(), contents);
+
+    // 小写形式含 ASCII 字符的非 ASCII 字符只有 U+0130（转为 i 加附加点）和
+    // 开尔文符号 U+212A（转为 k），查询不含 i 和 k 时 ASCII 折叠的结果就是完整的
+    if !query.contains(['i', 'k']) {
+        return hits;
+    }
+
+    let mut results = Vec::new();
+    let mut pos = 0;
+    for line in hits {
+        let start = line.as_ptr() as usize - contents.as_ptr() as usize;
+        folded_lines(&query, &contents[pos..start], &mut results);
+        results.push(line);
+        let end = start + line.len();
+        pos = memchr(b'\n', &contents.as_bytes()[end..]).map_or(contents.len(), |i| end + i + 1);
+    }
+    folded_lines(&query, &contents[pos..], &mut results);
+    results
+}
+
+// 两次命中之间的行在 ASCII 折叠下都不匹配，只有含 U+0130 或 U+212A 的行
+// 可能在完整的小写转换下匹配。按两者 UTF-8 编码的首字节查找候选行再逐行比较
+fn folded_lines<'a>(query: &str, region: &'a str, results: &mut Vec<&'a str>) {
+    let bytes = region.as_bytes();
+    let mut pos = 0;
+    while let Some(i) = memchr2(0xC4, 0xE2, &bytes[pos..]) {
+        let at = pos + i;
+        let start = memrchr(b'\n', &bytes[pos..at]).map_or(pos, |i| pos + i + 1);
+        let end = memchr(b'\n', &bytes[at..]).map_or(bytes.len(), |i| at + i);
+        // 与 lines() 一致，只去掉 \r\n 中的 \r
+        let mut line = &region[start..end];
+        if end < bytes.len() {
+            line = line.strip_suffix('\r').unwrap_or(line);
+        }
+        if line.to_lowercase().contains(query) {
+            results.push(line);
+        }
+        pos = (end + 1).min(bytes.len());
+    }
+}
+
+// 在 haystack 中查找 needle，返回 contents 中对应的整行。
+// haystack 与 contents 长度相同，且换行符位置一致
+fn literal_lines<'a>(needle: &[u8], haystack: &[u8], contents: &'a str) -> Vec<&'a str> {
+    let finder = memmem::Finder::new(needle);
+    let mut results = Vec::new();
+    let mut pos = 0;
+
+    while pos < haystack.len() {
+        let hit = match finder.find(&haystack[pos..]) {
+            Some(i) => pos + i,
+            None => break,
+        };
+
+        // 行首只需在上一行之后查找
+        let start = memrchr(b'\n', &haystack[pos..hit]).map_or(pos, |i| pos + i + 1);
+        let end = memchr(b'\n', &haystack[hit..]).map_or(haystack.len(), |i| hit + i);
+
+        // 与 lines() 一致，去掉 \r\n 中的 \r
+        let mut line_end = end;
+        if end < haystack.len() && line_end > start && haystack[line_end - 1] == b'\r' {
+            line_end -= 1;
+        }
+
+        // 跨越行尾的命中（查询中含有换行符）不算匹配
+        if hit + needle.len() <= line_end {
+            results.push(&contents[start..line_end]);
+        }
+
+        pos = end + 1;
+    }
+
+    results
+}
+
+// 返回一行中每一处（不重叠的）匹配文本，供 --only-matching 使用
+pub fn find_matches<'a>(query: &str, line: &'a str, case_sensitive: bool) -> Vec<&'a str> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    if case_sensitive {
+        return line.match_indices(query).map(|(_, m)| m).collect();
+    }
+
+    // 纯 ASCII 时可以直接在小写副本上定位，偏移量与原行一致
+    if query.is_ascii() && line.is_ascii() {
+        let lowered = line.to_ascii_lowercase();
+        let query = query.to_ascii_lowercase();
+        return lowered
+            .match_indices(&query)
+            .map(|(i, m)| &line[i..i + m.len()])
+            .collect();
+    }
+
+    // 否则逐个字符比较小写形式，匹配必须结束在字符边界上
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start < line.len() {
+        match lowercase_prefix_len(&line[start..], &query) {
+            Some(len) => {
+                matches.push(&line[start..start + len]);
+                start += len;
+            }
+            None => start += line[start..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+
+    matches
+}
+
+// text 开头的若干字符小写后恰好等于 query 时，返回这些字符的字节长度
+fn lowercase_prefix_len(text: &str, query: &[char]) -> Option<usize> {
+    let mut matched = 0;
+    for (i, c) in text.char_indices() {
+        for lower in c.to_lowercase() {
+            if query.get(matched) != Some(&lower) {
+                return None;
+            }
+            matched += 1;
+        }
+        if matched == query.len() {
+            return Some(i + c.len_utf8());
+        }
+    }
+
+    None
 }
 
 #[cfg(test)]
@@ -144,4 +786,57 @@ Trust me.";
             search_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn line_boundaries() {
+        let contents = "one fish\r\ntwo fish\nred\nblue fish fish";
+
+        assert_eq!(
+            vec!["one fish", "two fish", "blue fish fish"],
+            search("fish", contents)
+        );
+        assert_eq!(Vec::<&str>::new(), search("fish\ntwo", contents));
+        assert_eq!(vec!["RED"], search_insensitive("red", "RED\nGREEN"));
+        assert_eq!(vec!["Größe"], search_insensitive("GRÖ", "Größe\nGrenze"));
+    }
+
+    #[test]
+    fn ascii_lowercase_sources() {
+        // search_insensitive 依赖这一点：只有这两个非 ASCII 字符的小写形式含 ASCII 字符
+        let sources: Vec<char> = (0x80..=0x10FFFF)
+            .filter_map(char::from_u32)
+            .filter(|c| c.to_lowercase().any(|l| l.is_ascii()))
+            .collect();
+        assert_eq!(vec!['\u{130}', '\u{212A}'], sources);
+    }
+
+    #[test]
+    fn ascii_query_in_non_ascii_contents() {
+        // ASCII 命中与只有完整小写转换才匹配的行（开尔文符号）按原顺序返回
+        let contents = "Größe OK\r\nplain\n\u{212A}ELVIN\nkelvin\nÄrger\nokay";
+        assert_eq!(
+            vec!["Größe OK", "\u{212A}ELVIN", "kelvin", "okay"],
+            search_insensitive("k", contents)
+        );
+        assert_eq!(
+            vec!["\u{212A}ELVIN", "kelvin"],
+            search_insensitive("KELVIN", contents)
+        );
+        assert_eq!(
+            vec!["\u{130}stanbul\r"],
+            search_insensitive("i", "Ärger\n\u{130}stanbul\r")
+        );
+        assert_eq!(
+            vec!["Größe OK"],
+            search_insensitive("ok", "Größe OK\n\u{212A}")
+        );
+    }
+
+    #[test]
+    fn only_matching() {
+        assert_eq!(vec!["ab", "ab"], find_matches("ab", "abcab", true));
+        assert_eq!(vec!["Ab", "aB"], find_matches("ab", "Abc aB", false));
+        assert_eq!(vec!["GRÖ", "grö"], find_matches("Grö", "GRÖße grö", false));
+        assert_eq!(Vec::<&str>::new(), find_matches("ab", "Abc", true));
+    }
 }
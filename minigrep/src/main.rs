@@ -71,7 +71,23 @@ fn main() {
             process::exit(1);
         });
 
-        println!("Searching for {} from {}", config.query, config.file_path);
+        // 生成补全脚本或 man 手册时只输出生成的内容
+        if let Some(generate) = &config.generate {
+            match generate.render() {
+                Ok(output) => print!("{}", output),
+                Err(err) => {
+                    eprintln!("Problem parsing arguments: {}", err);
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+
+        println!(
+            "Searching for {} from {}",
+            config.query,
+            config.file_paths.join(", ")
+        );
 
         // 分离主体逻辑, 处理返回的错误
         if let Err(err) = run(config) {
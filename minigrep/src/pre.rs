@@ -0,0 +1,100 @@
+// 预处理钩子：把输入文件交给本地命令处理，在命令的标准输出上搜索
+
+use std::{io, path::Path, process::Command};
+
+// 以文件路径作为唯一参数运行 command，返回其标准输出
+pub fn preprocess(command: &str, path: &Path) -> io::Result<Vec<u8>> {
+    let output = Command::new(command).arg(path).output()?;
+
+    if !output.status.success() {
+        let mut message = format!(
+            "preprocessor {} failed on {} ({})",
+            command,
+            path.display(),
+            output.status
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.trim().is_empty() {
+            message.push_str(": ");
+            message.push_str(stderr.trim());
+        }
+        return Err(io::Error::other(message));
+    }
+
+    Ok(output.stdout)
+}
+
+// 是否需要预处理：未指定 --pre-glob 时处理所有文件。
+// 不含 / 的模式只匹配文件名，否则匹配完整路径
+pub fn should_preprocess(globs: &[String], path: &Path) -> bool {
+    if globs.is_empty() {
+        return true;
+    }
+
+    let full = path.to_string_lossy();
+    let name = path
+        .file_name()
+        .map_or_else(|| full.clone(), |name| name.to_string_lossy());
+
+    globs.iter().any(|glob| {
+        let text = if glob.contains('/') { &full } else { &name };
+        glob_match(glob.as_bytes(), text.as_bytes())
+    })
+}
+
+// 简单的通配符匹配：* 匹配任意长度，? 匹配单个字节。
+// 只记住最后一个 * 的位置和文本中重试的位置，失配时让这个 * 多吞一个字节，
+// 更早的 * 不必再回溯，时间为 O(模式长度 × 文本长度)
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    // 文本用完后，模式只能剩下 *
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_match(b"*.pdf", b"report.pdf"));
+        assert!(glob_match(b"doc?.txt", b"doc1.txt"));
+        assert!(!glob_match(b"*.pdf", b"report.pdf.txt"));
+        assert!(glob_match(b"a*b*c", b"aXbYbZc"));
+        assert!(glob_match(b"**", b""));
+        assert!(!glob_match(b"?", b""));
+
+        // 多个 * 不会导致指数级回溯
+        let name = vec![b'a'; 10_000];
+        assert!(!glob_match(b"*a*a*a*a*a*a*a*a*b", &name));
+
+        let globs = vec![String::from("*.pdf"), String::from("docs/*.odt")];
+        assert!(should_preprocess(&globs, Path::new("a/b/report.pdf")));
+        assert!(should_preprocess(&globs, Path::new("docs/notes.odt")));
+        assert!(!should_preprocess(&globs, Path::new("notes.odt")));
+        assert!(should_preprocess(&[], Path::new("notes.odt")));
+    }
+}
@@ -0,0 +1,248 @@
+// 布尔查询表达式：例如 error AND (timeout OR refused) NOT healthcheck
+//
+// 语法（优先级从低到高）：
+//   or    := and ("OR" and)*
+//   and   := unary (["AND"] unary)*      相邻的项隐含 AND，因此 "a NOT b" 即 a AND NOT b
+//   unary := "NOT" unary | primary
+//   primary := "(" or ")" | 单词 | "带引号的短语"
+// 关键字只识别大写形式，小写的 and/or/not 作为普通单词
+
+// 解析、求值和释放表达式树都是递归的，限制括号和 NOT 的嵌套层数，
+// 并限制词的个数（连续的 AND/OR 会生成同样深的左倾树），避免耗尽栈
+const MAX_DEPTH: usize = 256;
+const MAX_TERMS: usize = 1024;
+
+use crate::contains;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Term(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word(String),
+}
+
+impl Expr {
+    pub fn parse(query: &str) -> Result<Expr, &'static str> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+
+        let expr = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err("unexpected ) in query expression");
+        }
+
+        Ok(expr)
+    }
+
+    pub fn is_match(&self, text: &str, case_sensitive: bool) -> bool {
+        match self {
+            Expr::Term(term) => contains(term, text, case_sensitive),
+            Expr::Not(inner) => !inner.is_match(text, case_sensitive),
+            Expr::And(left, right) => {
+                left.is_match(text, case_sensitive) && right.is_match(text, case_sensitive)
+            }
+            Expr::Or(left, right) => {
+                left.is_match(text, case_sensitive) || right.is_match(text, case_sensitive)
+            }
+        }
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err("unterminated \" in query expression"),
+                    }
+                }
+                tokens.push(Token::Word(phrase));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+
+    if tokens.is_empty() {
+        return Err("empty query expression");
+    }
+    let terms = tokens
+        .iter()
+        .filter(|token| matches!(token, Token::Word(_)))
+        .count();
+    if terms > MAX_TERMS {
+        return Err("query expression has too many terms");
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // 当前所在的括号和 NOT 的层数
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn or(&mut self) -> Result<Expr, &'static str> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, &'static str> {
+        let mut expr = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                // 隐含的 AND
+                Some(Token::Not | Token::Open | Token::Word(_)) => (),
+                _ => break,
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, &'static str> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            let inner = self.nested(Parser::unary)?;
+            return Ok(Expr::Not(Box::new(inner)));
+        }
+        self.primary()
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Parser) -> Result<Expr, &'static str>,
+    ) -> Result<Expr, &'static str> {
+        if self.depth >= MAX_DEPTH {
+            return Err("query expression is nested too deeply");
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn primary(&mut self) -> Result<Expr, &'static str> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+
+        match token {
+            Some(Token::Word(word)) => Ok(Expr::Term(word)),
+            Some(Token::Open) => {
+                let expr = self.nested(Parser::or)?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("missing ) in query expression");
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(_) => Err("expected a term in query expression"),
+            None => Err("query expression ends unexpectedly"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(s: &str) -> Box<Expr> {
+        Box::new(Expr::Term(s.to_string()))
+    }
+
+    #[test]
+    fn parses_precedence_and_implicit_and() {
+        assert_eq!(
+            Expr::And(
+                Box::new(Expr::And(
+                    term("error"),
+                    Box::new(Expr::Or(term("timeout"), term("refused")))
+                )),
+                Box::new(Expr::Not(term("healthcheck")))
+            ),
+            Expr::parse("error AND (timeout OR refused) NOT healthcheck").unwrap()
+        );
+        assert_eq!(
+            Expr::Or(term("a"), Box::new(Expr::And(term("b"), term("c d")))),
+            Expr::parse("a OR b \"c d\"").unwrap()
+        );
+        assert!(Expr::parse("(a OR b").is_err());
+        assert!(Expr::parse("a OR").is_err());
+        assert!(Expr::parse("a)").is_err());
+        assert!(Expr::parse("a \"b c").is_err());
+        assert!(Expr::parse(&"(".repeat(100_000)).is_err());
+        assert!(Expr::parse(&"NOT ".repeat(100_000)).is_err());
+        assert!(Expr::parse(&"a ".repeat(100_000)).is_err());
+        assert!(Expr::parse(&format!("{}a{}", "(".repeat(200), ")".repeat(200))).is_ok());
+    }
+
+    #[test]
+    fn evaluates_per_text() {
+        let expr = Expr::parse("error AND (timeout OR refused) NOT healthcheck").unwrap();
+
+        assert!(expr.is_match("ERROR: connection refused", false));
+        assert!(!expr.is_match("ERROR: connection refused", true));
+        assert!(!expr.is_match("error: healthcheck timeout", false));
+        assert!(!expr.is_match("error: disk full", false));
+    }
+}
@@ -0,0 +1,118 @@
+// 记录模式：把多行内容作为一个整体来匹配和打印，
+// 例如一条带有 Java 堆栈的日志
+
+use regex::Regex;
+
+#[derive(Debug, Clone)]
+pub enum RecordSeparator {
+    // 以空行分隔（段落）
+    Blank,
+    // 每条与该正则匹配的行开始一条新记录
+    Start(Regex),
+}
+
+impl RecordSeparator {
+    // "blank" 表示空行分隔，其余值按正则表达式解析
+    pub fn parse(value: &str) -> Result<RecordSeparator, &'static str> {
+        if value == "blank" {
+            return Ok(RecordSeparator::Blank);
+        }
+
+        Regex::new(value)
+            .map(RecordSeparator::Start)
+            .map_err(|_| "--record-separator is not a valid regex")
+    }
+
+    pub fn split<'a>(&self, contents: &'a str) -> Vec<&'a str> {
+        match self {
+            RecordSeparator::Blank => paragraphs(contents),
+            RecordSeparator::Start(start) => records_starting_at(contents, start),
+        }
+    }
+}
+
+// 按空行切分段落，返回的每一段不含结尾的换行符
+pub fn paragraphs(contents: &str) -> Vec<&str> {
+    let mut records = Vec::new();
+    let mut start = None;
+    let mut end = 0;
+    let mut pos = 0;
+
+    for line in contents.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            if let Some(s) = start.take() {
+                records.push(&contents[s..end]);
+            }
+        } else {
+            start.get_or_insert(pos);
+            end = pos + line.trim_end_matches(['\n', '\r']).len();
+        }
+        pos += line.len();
+    }
+
+    if let Some(s) = start {
+        records.push(&contents[s..end]);
+    }
+
+    records
+}
+
+// 第一条起始行之前的内容单独作为一条记录；返回的记录不含结尾的换行符
+pub fn records_starting_at<'a>(contents: &'a str, start: &Regex) -> Vec<&'a str> {
+    let mut records = Vec::new();
+    let mut record_start = 0;
+    let mut pos = 0;
+
+    for line in contents.split_inclusive('\n') {
+        if pos > record_start && start.is_match(line.trim_end_matches(['\n', '\r'])) {
+            records.push(trim_newline(&contents[record_start..pos]));
+            record_start = pos;
+        }
+        pos += line.len();
+    }
+
+    if record_start < contents.len() {
+        records.push(trim_newline(&contents[record_start..]));
+    }
+
+    records
+}
+
+fn trim_newline(record: &str) -> &str {
+    let record = record.strip_suffix('\n').unwrap_or(record);
+    record.strip_suffix('\r').unwrap_or(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_blank_lines() {
+        let contents = "\n\na\nb\n\n  \nc\r\n\nd";
+
+        assert_eq!(vec!["a\nb", "c", "d"], paragraphs(contents));
+    }
+
+    #[test]
+    fn splits_on_start_pattern() {
+        let separator = RecordSeparator::parse(r"^\d{4}-\d{2}-\d{2}").unwrap();
+        let contents = "\
+preamble
+2024-01-01 ERROR boom
+java.lang.IllegalStateException: bad
+    at com.example.Main.main(Main.java:3)
+2024-01-01 INFO ok
+";
+
+        assert_eq!(
+            vec![
+                "preamble",
+                "2024-01-01 ERROR boom\njava.lang.IllegalStateException: bad\n    at com.example.Main.main(Main.java:3)",
+                "2024-01-01 INFO ok",
+            ],
+            separator.split(contents)
+        );
+        assert!(RecordSeparator::parse("(").is_err());
+    }
+}
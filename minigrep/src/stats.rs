@@ -0,0 +1,52 @@
+// --stats：在 run 结束后打印的统计信息，按阶段记录耗时
+
+use std::{fmt, time::Duration};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Stats {
+    pub files_searched: usize,
+    pub bytes_read: usize,
+    pub lines_scanned: usize,
+    // 匹配的行（记录模式下为记录）数
+    pub matching_lines: usize,
+    // 匹配出现的次数，一行中可能有多处
+    pub matches: usize,
+    pub read_time: Duration,
+    pub search_time: Duration,
+    pub print_time: Duration,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} files searched", self.files_searched)?;
+        writeln!(f, "{} bytes read", self.bytes_read)?;
+        writeln!(f, "{} lines scanned", self.lines_scanned)?;
+        writeln!(f, "{} matching lines", self.matching_lines)?;
+        writeln!(f, "{} matches", self.matches)?;
+        writeln!(f, "{:.6} seconds reading", self.read_time.as_secs_f64())?;
+        writeln!(f, "{:.6} seconds searching", self.search_time.as_secs_f64())?;
+        write!(f, "{:.6} seconds printing", self.print_time.as_secs_f64())
+    }
+}
+
+// 与 lines() 的计数方式一致：末尾的换行符不产生额外的空行
+pub fn count_lines(contents: &str) -> usize {
+    let newlines = memchr::memchr_iter(b'\n', contents.as_bytes()).count();
+    if contents.is_empty() || contents.ends_with('\n') {
+        newlines
+    } else {
+        newlines + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_lines_like_lines() {
+        for contents in ["", "a", "a\n", "a\nb", "a\n\nb\n", "\n"] {
+            assert_eq!(contents.lines().count(), count_lines(contents));
+        }
+    }
+}
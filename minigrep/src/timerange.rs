@@ -0,0 +1,276 @@
+// 按行首时间戳过滤：--since / --until 限定时间窗口。
+// 没有时间戳的行（例如堆栈的后续行）沿用上一条带时间戳的行的时间
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use memchr::{memchr, memrchr};
+use std::ops::Range;
+
+// 常见的不带时区的行首格式；不带时区的时间按本地时间解释
+const NAIVE_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    // nginx error.log
+    "%Y/%m/%d %H:%M:%S",
+    // syslog 的日期部分不含年份，解析前补上当前年份
+    "%Y %b %e %H:%M:%S",
+];
+
+#[derive(Debug, Clone)]
+pub struct TimeRange {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    // 用户指定的 strftime 格式，优先于内置格式
+    pub format: Option<String>,
+    // 时间戳按升序排列时可以二分查找窗口，而不必从头逐行解析（文件仍然整个读入）
+    pub sorted: bool,
+    // syslog 时间戳不含年份，补上的当前年份只在构建时取一次
+    year: i32,
+}
+
+impl TimeRange {
+    pub fn build(
+        since: Option<&str>,
+        until: Option<&str>,
+        format: Option<String>,
+        sorted: bool,
+    ) -> Result<TimeRange, &'static str> {
+        let year = Local::now().year();
+        let parse = |value: &str| {
+            // 命令行上还允许只写日期
+            parse_with_year(value, format.as_deref(), year).or_else(|| {
+                let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+                local_to_utc(date.and_hms_opt(0, 0, 0)?)
+            })
+        };
+
+        let since = match since {
+            Some(value) => Some(parse(value).ok_or("invalid --since timestamp")?),
+            None => None,
+        };
+        let until = match until {
+            Some(value) => Some(parse(value).ok_or("invalid --until timestamp")?),
+            None => None,
+        };
+
+        Ok(TimeRange {
+            since,
+            until,
+            format,
+            sorted,
+            year,
+        })
+    }
+
+    fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| time >= since) && self.until.is_none_or(|until| time <= until)
+    }
+
+    fn line_time(&self, line: &str) -> Option<DateTime<Utc>> {
+        parse_with_year(line, self.format.as_deref(), self.year)
+    }
+
+    // 返回落在时间窗口内的字节区间，相邻的行合并为一个区间
+    pub fn spans(&self, contents: &str) -> Vec<Range<usize>> {
+        if self.sorted {
+            let start = match self.since {
+                Some(since) => self.lower_bound(contents, |time| time >= since),
+                None => 0,
+            };
+            let end = match self.until {
+                Some(until) => self.lower_bound(contents, |time| time > until),
+                None => contents.len(),
+            };
+            // 窗口开头之前的无时间戳行不属于窗口
+            let start = self.first_timed_line(contents, start, end).unwrap_or(end);
+            let mut spans = Vec::new();
+            if start < end {
+                spans.push(start..end);
+            }
+            return spans;
+        }
+
+        let mut spans: Vec<Range<usize>> = Vec::new();
+        let mut inside = false;
+        let mut pos = 0;
+        while pos < contents.len() {
+            let end =
+                memchr(b'\n', &contents.as_bytes()[pos..]).map_or(contents.len(), |i| pos + i + 1);
+            if let Some(time) = self.line_time(&contents[pos..end]) {
+                inside = self.contains(time);
+            }
+            if inside {
+                match spans.last_mut() {
+                    Some(last) if last.end == pos => last.end = end,
+                    _ => spans.push(pos..end),
+                }
+            }
+            pos = end;
+        }
+
+        spans
+    }
+
+    // 二分查找第一条满足 pred 的带时间戳的行，返回其行首偏移量（找不到时返回末尾）
+    fn lower_bound(&self, contents: &str, pred: impl Fn(DateTime<Utc>) -> bool) -> usize {
+        let bytes = contents.as_bytes();
+        let mut found = contents.len();
+        // 答案只可能在 [lo, hi) 之内，或者就是 found
+        let mut lo = 0;
+        let mut hi = contents.len();
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let start = memrchr(b'\n', &bytes[lo..mid]).map_or(lo, |i| lo + i + 1);
+
+            match self.first_timed_line(contents, start, hi) {
+                Some(line) => {
+                    let next =
+                        memchr(b'\n', &bytes[line..]).map_or(contents.len(), |i| line + i + 1);
+                    let time = self.line_time(&contents[line..next]).unwrap();
+                    if pred(time) {
+                        found = line;
+                        hi = line;
+                    } else {
+                        lo = next;
+                    }
+                }
+                // [start, hi) 中没有带时间戳的行，继续在前半部分查找
+                None => hi = start,
+            }
+        }
+
+        found
+    }
+
+    // [from, to) 内第一条带时间戳的行的行首偏移量，from 必须是行首
+    fn first_timed_line(&self, contents: &str, from: usize, to: usize) -> Option<usize> {
+        let mut pos = from;
+        while pos < to {
+            let end =
+                memchr(b'\n', &contents.as_bytes()[pos..]).map_or(contents.len(), |i| pos + i + 1);
+            if self.line_time(&contents[pos..end]).is_some() {
+                return Some(pos);
+            }
+            pos = end;
+        }
+
+        None
+    }
+}
+
+// 解析行首的时间戳：用户格式、RFC 3339、常见的 syslog/nginx 格式
+pub fn parse_timestamp(line: &str, format: Option<&str>) -> Option<DateTime<Utc>> {
+    parse_with_year(line, format, Local::now().year())
+}
+
+// 逐行解析时由 TimeRange 传入年份，避免每行都查询当前时间
+fn parse_with_year(line: &str, format: Option<&str>, year: i32) -> Option<DateTime<Utc>> {
+    if let Some(format) = format {
+        if let Ok((time, _)) = DateTime::parse_and_remainder(line, format) {
+            return Some(time.with_timezone(&Utc));
+        }
+        let (naive, _) = NaiveDateTime::parse_and_remainder(line, format).ok()?;
+        return local_to_utc(naive);
+    }
+
+    let first = line.split_whitespace().next()?;
+    if let Ok(time) = DateTime::parse_from_rfc3339(first) {
+        return Some(time.with_timezone(&Utc));
+    }
+
+    // nginx access.log 风格：[10/Oct/2000:13:55:36 -0700]
+    if line.starts_with('[') {
+        if let Ok((time, _)) = DateTime::parse_and_remainder(line, "[%d/%b/%Y:%H:%M:%S %z]") {
+            return Some(time.with_timezone(&Utc));
+        }
+    }
+
+    NAIVE_FORMATS.iter().find_map(|format| {
+        let naive = if format.starts_with("%Y %b") {
+            // 只有尝试 syslog 格式时才补年份，并且只复制时间戳可能占用的行首部分
+            let with_year = format!("{} {}", year, syslog_prefix(line)?);
+            NaiveDateTime::parse_and_remainder(&with_year, format)
+                .ok()?
+                .0
+        } else {
+            NaiveDateTime::parse_and_remainder(line, format).ok()?.0
+        };
+        local_to_utc(naive)
+    })
+}
+
+// syslog 时间戳形如 "Jan  2 03:04:05"，以月份缩写开头，最多占 16 个字节
+fn syslog_prefix(line: &str) -> Option<&str> {
+    if !line.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let mut end = line.len().min(16);
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(&line[..end])
+}
+
+fn local_to_utc(naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_common_formats() {
+        let utc = |s| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+
+        assert_eq!(
+            Some(utc("2024-01-02T03:04:05Z")),
+            parse_timestamp("2024-01-02T03:04:05Z INFO ready", None)
+        );
+        assert_eq!(
+            Some(utc("2000-10-10T20:55:36Z")),
+            parse_timestamp("[10/Oct/2000:13:55:36 -0700] GET /", None)
+        );
+        assert!(parse_timestamp("Jan  2 03:04:05 host sshd[1]: hi", None).is_some());
+        assert!(parse_timestamp("2024/01/02 03:04:05 [error] 1#1: boom", None).is_some());
+        assert!(parse_timestamp("02.01.2024 03:04 custom", Some("%d.%m.%Y %H:%M")).is_some());
+        assert_eq!(None, parse_timestamp("    at com.example.Main", None));
+        assert!(parse_timestamp("Jan  2 03:04:05€ cut inside a char", None).is_some());
+    }
+
+    #[test]
+    fn sorted_and_unsorted_windows_agree() {
+        let contents = "\
+2024-01-01T00:00:00Z boot
+2024-01-02T00:00:00Z error: timeout
+    at frame one
+2024-01-03T00:00:00Z error: refused
+    at frame two
+2024-01-04T00:00:00Z shutdown
+";
+        let build = |sorted| {
+            TimeRange::build(
+                Some("2024-01-02T00:00:00Z"),
+                Some("2024-01-03T12:00:00Z"),
+                None,
+                sorted,
+            )
+            .unwrap()
+        };
+
+        let expected = "\
+2024-01-02T00:00:00Z error: timeout
+    at frame one
+2024-01-03T00:00:00Z error: refused
+    at frame two
+";
+        for sorted in [true, false] {
+            let spans = build(sorted).spans(contents);
+            assert_eq!(1, spans.len());
+            assert_eq!(expected, &contents[spans[0].clone()]);
+        }
+    }
+}
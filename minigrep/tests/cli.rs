@@ -0,0 +1,494 @@
+// 集成测试：运行编译好的 minigrep，检查标准输出、标准错误和退出码
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn minigrep(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(args)
+        .current_dir(FIXTURES)
+        .env_remove("CASE_SENSITIVE")
+        .output()
+        .expect("failed to run minigrep")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn searches_single_file() {
+    let output = minigrep(&["to", "tree/poem.txt"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        "\
+Searching for to from tree/poem.txt
+Are you nobody, too?
+How dreary to be somebody!
+To tell your name the livelong day
+To an admiring bog!
+",
+        stdout(&output)
+    );
+    assert_eq!("", stderr(&output));
+}
+
+#[test]
+fn respects_case_sensitive_env() {
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["To", "tree/poem.txt"])
+        .current_dir(FIXTURES)
+        .env("CASE_SENSITIVE", "1")
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        "\
+Searching for To from tree/poem.txt
+To tell your name the livelong day
+To an admiring bog!
+",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn searches_directory_tree_with_file_names() {
+    let output = minigrep(&["frog", "tree", "--sort", "path"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        "\
+Searching for frog from tree
+tree/nested/notes.md:Notes about the frog
+tree/poem.txt:How public, like a frog
+",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn handles_binary_files() {
+    let output = minigrep(&["frog", "binary.bin"]);
+    assert_eq!(
+        "Searching for frog from binary.bin\nBinary file binary.bin matches\n",
+        stdout(&output)
+    );
+
+    let output = minigrep(&["frog", "binary.bin", "--binary-files=without-match"]);
+    assert_eq!("Searching for frog from binary.bin\n", stdout(&output));
+
+    let output = minigrep(&["frog", "binary.bin", "--binary-files", "text"]);
+    assert!(stdout(&output).contains("frog inside a binary"));
+}
+
+#[test]
+fn structured_modes_match_selected_fields() {
+    let output = minigrep(&[
+        "failed",
+        "tree/nested/deep/jobs.csv",
+        "--csv",
+        "--field",
+        "status",
+    ]);
+    assert_eq!(
+        "Searching for failed from tree/nested/deep/jobs.csv\nbuild,failed,\"retry, then ok\"\n",
+        stdout(&output)
+    );
+
+    let output = minigrep(&["error", "tree/logs/app.jsonl", "--json-path", "$.level"]);
+    assert_eq!(
+        "Searching for error from tree/logs/app.jsonl\n{\"level\":\"error\",\"msg\":\"disk full\"}\n",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn only_matching_and_unique() {
+    let output = minigrep(&["body", "tree/poem.txt", "-o", "--unique"]);
+
+    assert_eq!(
+        "Searching for body from tree/poem.txt\nbody\n",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn filters_lines_by_time_window() {
+    // 无时间戳的堆栈行沿用上一行的时间；二分查找与逐行扫描结果一致
+    for sorted in [false, true] {
+        let mut args = vec![
+            "frame",
+            "server.log",
+            "--since",
+            "2024-01-02T00:00:00Z",
+            "--until",
+            "2024-01-03T12:00:00Z",
+        ];
+        if sorted {
+            args.push("--time-sorted");
+        }
+        let output = minigrep(&args);
+
+        assert!(output.status.success());
+        assert_eq!(
+            "\
+Searching for frame from server.log
+    at frame one
+    at frame two
+",
+            stdout(&output)
+        );
+    }
+}
+
+#[test]
+fn searches_preprocessor_output() {
+    // sort 的输出顺序与原文件不同，可以看出搜索的是预处理的结果
+    let output = minigrep(&["to", "tree/poem.txt", "--pre", "sort"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        "\
+Searching for to from tree/poem.txt
+Are you nobody, too?
+How dreary to be somebody!
+To an admiring bog!
+To tell your name the livelong day
+",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn evaluates_boolean_queries() {
+    let output = minigrep(&["error NOT (timeout OR disk)", "server.log", "--bool"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        "\
+Searching for error NOT (timeout OR disk) from server.log
+2024-01-03T00:00:00Z error: refused
+",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn matches_whole_records() {
+    let output = minigrep(&["refused", "server.log", "--record-separator", r"^\d{4}-"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        "\
+Searching for refused from server.log
+2024-01-03T00:00:00Z error: refused
+    at frame two
+",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn prints_statistics() {
+    let output = minigrep(&["error", "server.log", "--stats"]);
+
+    assert!(output.status.success());
+    let stdout = stdout(&output);
+    // 计时随运行而变，只检查计数和计时行的格式
+    assert!(stdout.starts_with(
+        "\
+Searching for error from server.log
+2024-01-02T00:00:00Z error: timeout
+2024-01-03T00:00:00Z error: refused
+2024-01-04T00:00:00Z error: disk full
+
+1 files searched
+192 bytes read
+7 lines scanned
+3 matching lines
+3 matches
+"
+    ));
+    for phase in ["reading", "searching", "printing"] {
+        assert!(stdout.contains(&format!(" seconds {}", phase)));
+    }
+}
+
+#[test]
+fn reports_usage_errors() {
+    let output = minigrep(&[]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("", stdout(&output));
+    assert_eq!(
+        "Problem parsing arguments: Didn't get query string\n",
+        stderr(&output)
+    );
+
+    let output = minigrep(&["frog"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "Problem parsing arguments: Didn't get file path string\n",
+        stderr(&output)
+    );
+
+    let output = minigrep(&["frog", "tree", "--sort=size"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "Problem parsing arguments: --sort must be one of: path, modified, none\n",
+        stderr(&output)
+    );
+
+    let output = minigrep(&["frog", "tree", "--nope"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "Problem parsing arguments: unknown option --nope (put -- before a query that starts with -)\n",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn searches_for_queries_that_look_like_options() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp.path().join("diff.txt"),
+        "-5 lines\n--verbose\n+5 lines\n",
+    )
+    .unwrap();
+
+    let output = minigrep_in(tmp.path(), &["-5", "diff.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "Searching for -5 from diff.txt\n-5 lines\n",
+        stdout(&output)
+    );
+
+    let output = minigrep_in(tmp.path(), &["--", "--verbose", "diff.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "Searching for --verbose from diff.txt\n--verbose\n",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn reports_application_errors() {
+    let output = minigrep(&["frog", "missing.txt"]);
+
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("Searching for frog from missing.txt\n", stdout(&output));
+    assert!(stderr(&output).starts_with("Application error: "));
+}
+
+#[test]
+fn generates_docs_without_searching() {
+    let output = minigrep(&["--generate", "man"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with(".TH MINIGREP 1"));
+
+    let output = minigrep(&["--generate", "completions", "fish"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("complete -c minigrep -l sort"));
+
+    let output = minigrep(&["--generate", "completions", "tcsh"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(stderr(&output).contains("unsupported shell tcsh"));
+}
+
+#[test]
+fn searches_archive_members() {
+    let output = minigrep(&["frog", "bundle.zip", "bundle.tar.gz"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        format!(
+            "\
+Searching for frog from bundle.zip, bundle.tar.gz
+bundle.zip!/logs/build.log:step 2: frog jumped
+bundle.tar.gz!/inner/{}.txt:the frog in a tarball
+",
+            "x".repeat(110)
+        ),
+        stdout(&output)
+    );
+}
+
+#[test]
+fn searches_unreadable_archives_as_plain_files() {
+    let output = minigrep(&["frog", "notes.zip", "tree/poem.txt"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        "\
+Searching for frog from notes.zip, tree/poem.txt
+notes.zip:frog notes that only look like an archive
+tree/poem.txt:How public, like a frog
+",
+        stdout(&output)
+    );
+    assert!(stderr(&output).contains("notes.zip: corrupt zip archive"));
+}
+
+// 用 git 命令建一个小仓库：前两个提交打包成 REF_DELTA 的包，接下来两个增量打包成
+// OFS_DELTA 的包，最后一个提交保持松散对象
+fn git(dir: &Path, args: &[&str], day: usize) -> String {
+    let date = format!("2024-01-0{}T00:00:00Z", day);
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .env("GIT_AUTHOR_DATE", &date)
+        .env("GIT_COMMITTER_DATE", &date)
+        .output()
+        .expect("failed to run git");
+    assert!(
+        output.status.success(),
+        "git {:?}: {}",
+        args,
+        stderr(&output)
+    );
+    stdout(&output).trim().to_string()
+}
+
+fn pond_repo(dir: &Path) {
+    let pond = |day: usize, frog: &str| {
+        let mut contents: String = (1..=60)
+            .map(|i| format!("line {} of the pond\n", i))
+            .collect();
+        contents.push_str(&format!("frog {}\n", frog));
+        fs::write(dir.join("pond.txt"), contents).unwrap();
+        git(dir, &["add", "."], day);
+        git(dir, &["commit", "-qm", frog], day);
+    };
+
+    git(dir, &["init", "-q"], 1);
+    pond(1, "one");
+    pond(2, "two");
+    git(
+        dir,
+        &["-c", "repack.useDeltaBaseOffset=false", "repack", "-adfq"],
+        2,
+    );
+    pond(3, "three");
+    pond(4, "four");
+    git(dir, &["repack", "-dq"], 4);
+    pond(5, "five");
+}
+
+fn minigrep_in(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("CASE_SENSITIVE")
+        .output()
+        .expect("failed to run minigrep")
+}
+
+#[test]
+fn searches_git_revisions() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = tmp.path().join("repo");
+    fs::create_dir(&repo).unwrap();
+    pond_repo(&repo);
+    let short = |rev: &str| git(&repo, &["rev-parse", "--short=7", rev], 5);
+
+    let output = minigrep_in(&repo, &["frog", "pond.txt", "--rev", "HEAD~3"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        "Searching for frog from pond.txt\nHEAD~3:pond.txt:frog two\n",
+        stdout(&output)
+    );
+
+    let output = minigrep_in(&repo, &["frog", "--all-revs"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let expected: String = ["HEAD", "HEAD~1", "HEAD~2", "HEAD~3", "HEAD~4"]
+        .iter()
+        .zip(["five", "four", "three", "two", "one"])
+        .map(|(rev, frog)| format!("{}:pond.txt:frog {}\n", short(rev), frog))
+        .collect();
+    assert_eq!(
+        format!("Searching for frog from .\n{}", expected),
+        stdout(&output)
+    );
+
+    // 链接的工作树通过 commondir 找到主仓库的对象和引用
+    let worktree = tmp.path().join("wt");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            worktree.to_str().unwrap(),
+            "HEAD~1",
+        ],
+        5,
+    );
+    let output = minigrep_in(&worktree, &["frog", "pond.txt", "--rev", "HEAD"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        "Searching for frog from pond.txt\nHEAD:pond.txt:frog four\n",
+        stdout(&output)
+    );
+
+    // 引用名不能指向仓库之外的文件
+    let output = minigrep_in(&repo, &["root", "pond.txt", "--rev", "/etc/passwd"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(stderr(&output).contains("invalid ref name /etc/passwd"));
+}
+
+#[test]
+fn searches_all_revisions_of_shallow_clones() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = tmp.path().join("repo");
+    fs::create_dir(&repo).unwrap();
+    pond_repo(&repo);
+
+    // 本地路径要写成 file:// 形式，--depth 才会生效
+    let url = format!("file://{}", repo.display());
+    git(
+        tmp.path(),
+        &["clone", "-q", "--depth", "2", &url, "shallow"],
+        5,
+    );
+    let shallow = tmp.path().join("shallow");
+    let short = |rev: &str| git(&shallow, &["rev-parse", "--short=7", rev], 5);
+
+    let output = minigrep_in(&shallow, &["frog", "--all-revs"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        format!(
+            "Searching for frog from .\n{}:pond.txt:frog five\n{}:pond.txt:frog four\n",
+            short("HEAD"),
+            short("HEAD~1")
+        ),
+        stdout(&output)
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn does_not_follow_directory_symlinks() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "frog\n").unwrap();
+    std::os::unix::fs::symlink(".", tmp.path().join("loop")).unwrap();
+    std::os::unix::fs::symlink("a.txt", tmp.path().join("b.txt")).unwrap();
+
+    let output = minigrep_in(tmp.path(), &["frog", ".", "--sort", "path"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        "Searching for frog from .\n./a.txt:frog\n./b.txt:frog\n",
+        stdout(&output)
+    );
+}
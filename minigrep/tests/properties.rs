@@ -0,0 +1,84 @@
+// 属性测试：用随机输入检查搜索结果之间应当成立的关系
+
+use minigrep::{search, search_insensitive};
+use proptest::prelude::*;
+use std::{fs, process::Command};
+
+// 字母表较小，随机内容中才容易出现匹配；包含大小写、非 ASCII 字符和 \r\n
+fn text() -> impl Strategy<Value = String> {
+    proptest::string::string_regex("[abAB ßẞé\r\n]{0,64}").unwrap()
+}
+
+fn query() -> impl Strategy<Value = String> {
+    proptest::string::string_regex("[abAB ßé]{1,3}").unwrap()
+}
+
+proptest! {
+    #[test]
+    fn buffer_search_matches_line_by_line(query in query(), contents in text()) {
+        let expected: Vec<&str> = contents
+            .lines()
+            .filter(|line| line.contains(query.as_str()))
+            .collect();
+
+        prop_assert_eq!(expected, search(&query, &contents));
+    }
+
+    // 开尔文符号和 İ 的小写形式含 ASCII 字符，ASCII 查询也要对它们所在的行做完整比较
+    #[test]
+    fn insensitive_matches_line_by_line(
+        query in "[ikIK ]{1,3}",
+        contents in "[ikIK \u{130}\u{212A}é\r\n]{0,64}",
+    ) {
+        let lowered = query.to_lowercase();
+        let expected: Vec<&str> = contents
+            .lines()
+            .filter(|line| line.to_lowercase().contains(&lowered))
+            .collect();
+
+        prop_assert_eq!(expected, search_insensitive(&query, &contents));
+    }
+
+    #[test]
+    fn insensitive_is_superset_of_sensitive(query in query(), contents in text()) {
+        let sensitive = search(&query, &contents);
+        let insensitive = search_insensitive(&query, &contents);
+
+        // 按顺序逐个出现在不区分大小写的结果中
+        let mut rest = insensitive.iter();
+        for line in &sensitive {
+            prop_assert!(rest.any(|other| std::ptr::eq(*other, *line)));
+        }
+    }
+}
+
+proptest! {
+    // 每个用例都要启动一次进程，减少用例数
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    // 默认顺序（--sort none）保留命令行参数的顺序；目录内的文件按名字遍历，
+    // 不依赖 read_dir 返回的顺序（随文件系统和创建顺序而变），因此与 --sort path 的输出相同
+    #[test]
+    fn default_directory_order_is_deterministic(
+        files in proptest::collection::btree_map("[a-z]{1,8}", text(), 1..8),
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        for (i, (name, contents)) in files.iter().enumerate() {
+            let nested = dir.path().join(format!("d{}", i % 2));
+            fs::create_dir_all(&nested).unwrap();
+            fs::write(nested.join(format!("{}.txt", name)), contents).unwrap();
+        }
+
+        let run = |extra: &[&str]| {
+            let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+                .args(["a", "."])
+                .args(extra)
+                .current_dir(dir.path())
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        prop_assert_eq!(run(&["--sort", "path"]), run(&[]));
+    }
+}
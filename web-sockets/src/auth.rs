@@ -0,0 +1,278 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::header::{
+    HeaderValue, AUTHORIZATION, SEC_WEBSOCKET_PROTOCOL, WWW_AUTHENTICATE,
+};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+
+use crate::router::{self, parse_query};
+
+// 浏览器无法设置 Authorization 头，可以把令牌放在子协议中：Sec-WebSocket-Protocol: bearer.<token>
+const PROTOCOL_PREFIX: &str = "bearer.";
+
+// 认证通过后附加到连接上下文中的身份
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    pub subject: String,
+}
+
+// 认证方式：校验令牌，返回对应的身份
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, token: &str) -> Option<Identity>;
+}
+
+// 静态令牌文件，每行 "<token> <subject>"，subject 省略时为 token 本身；# 开头的行是注释
+pub struct TokenFile {
+    tokens: HashMap<String, String>,
+}
+
+impl TokenFile {
+    pub fn load(path: &Path) -> Result<TokenFile, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let tokens = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (token, subject) = line.split_once(char::is_whitespace).unwrap_or((line, line));
+                (token.to_string(), subject.trim().to_string())
+            })
+            .collect();
+        Ok(TokenFile { tokens })
+    }
+}
+
+impl Authenticator for TokenFile {
+    fn authenticate(&self, token: &str) -> Option<Identity> {
+        self.tokens.get(token).map(|subject| Identity {
+            subject: subject.clone(),
+        })
+    }
+}
+
+// HMAC 签名令牌："<subject>.<过期时间的 Unix 秒数>.<base64url(HMAC-SHA256(secret, subject.expires))>"
+pub struct HmacTokens {
+    secret: Vec<u8>,
+}
+
+impl HmacTokens {
+    pub fn new(secret: &[u8]) -> HmacTokens {
+        HmacTokens {
+            secret: secret.to_vec(),
+        }
+    }
+
+    // 密钥文件首尾的空白会被去掉，方便用 echo 生成
+    pub fn load(path: &Path) -> Result<HmacTokens, String> {
+        let secret = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let secret = secret.trim_ascii();
+        if secret.is_empty() {
+            return Err(format!("{}: empty HMAC secret", path.display()));
+        }
+        Ok(HmacTokens::new(secret))
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    // 签发在 ttl 之后过期的令牌
+    pub fn issue(&self, subject: &str, ttl: Duration) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.sign(subject, (now + ttl).as_secs())
+    }
+
+    fn sign(&self, subject: &str, expires: u64) -> String {
+        let payload = format!("{}.{}", subject, expires);
+        let signature = self.mac(&payload).finalize().into_bytes();
+        format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature))
+    }
+}
+
+impl Authenticator for HmacTokens {
+    fn authenticate(&self, token: &str) -> Option<Identity> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        let (subject, expires) = payload.rsplit_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        // verify_slice 使用常量时间比较
+        self.mac(payload).verify_slice(&signature).ok()?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        if expires.parse::<u64>().ok()? <= now {
+            return None;
+        }
+        Some(Identity {
+            subject: subject.to_string(),
+        })
+    }
+}
+
+// 握手时的认证钩子，依次尝试配置的认证方式；没有配置任何方式时不要求认证
+#[derive(Default)]
+pub struct Auth {
+    methods: Vec<Box<dyn Authenticator>>,
+}
+
+impl Auth {
+    pub fn new() -> Auth {
+        Auth::default()
+    }
+
+    pub fn with(mut self, method: impl Authenticator + 'static) -> Auth {
+        self.methods.push(Box::new(method));
+        self
+    }
+
+    // 认证通过时返回身份（未启用认证时为 None），失败时返回 401 响应。
+    // 令牌来自子协议时需要在响应中选择一个子协议，否则浏览器会断开连接。
+    // 错误类型与 tungstenite 的握手回调一致
+    #[allow(clippy::result_large_err)]
+    pub fn check(
+        &self,
+        request: &Request,
+        response: &mut Response,
+    ) -> Result<Option<Identity>, ErrorResponse> {
+        if self.methods.is_empty() {
+            return Ok(None);
+        }
+
+        let (token, protocol) = token(request).ok_or_else(|| unauthorized("missing token"))?;
+        let identity = self
+            .methods
+            .iter()
+            .find_map(|method| method.authenticate(&token))
+            .ok_or_else(|| unauthorized("invalid token"))?;
+
+        if let Some(protocol) = protocol {
+            response
+                .headers_mut()
+                .insert(SEC_WEBSOCKET_PROTOCOL, protocol);
+        }
+        Ok(Some(identity))
+    }
+}
+
+// 依次从 Authorization 头、?token= 查询参数和子协议中取令牌，
+// 第二个返回值是需要在响应中选择的子协议
+fn token(request: &Request) -> Option<(String, Option<HeaderValue>)> {
+    let headers = request.headers();
+
+    if let Some(value) = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+        if let Some(token) = value
+            .strip_prefix("Bearer ")
+            .or_else(|| value.strip_prefix("bearer "))
+        {
+            return Some((token.trim().to_string(), None));
+        }
+    }
+
+    if let Some(token) = parse_query(request.uri().query().unwrap_or("")).remove("token") {
+        return Some((token, None));
+    }
+
+    // 如果客户端还请求了其他子协议，选择第一个；否则原样选择携带令牌的子协议
+    let protocols: Vec<&str> = headers
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .collect();
+    let entry = protocols.iter().find(|p| p.starts_with(PROTOCOL_PREFIX))?;
+    let selected = protocols
+        .iter()
+        .find(|p| !p.starts_with(PROTOCOL_PREFIX))
+        .unwrap_or(entry);
+    Some((
+        entry[PROTOCOL_PREFIX.len()..].to_string(),
+        HeaderValue::from_str(selected).ok(),
+    ))
+}
+
+fn unauthorized(reason: &str) -> ErrorResponse {
+    let mut response = router::reject(StatusCode::UNAUTHORIZED, reason);
+    response
+        .headers_mut()
+        .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> Request {
+        let mut builder = Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap()
+    }
+
+    fn static_tokens() -> TokenFile {
+        TokenFile {
+            tokens: [("s3cret".to_string(), "alice".to_string())].into(),
+        }
+    }
+
+    #[test]
+    fn finds_token_in_header_query_or_protocol() {
+        let auth = Auth::new().with(static_tokens());
+        let alice = Some(Identity {
+            subject: "alice".into(),
+        });
+
+        let mut response = Response::default();
+        let req = request("/", &[("Authorization", "Bearer s3cret")]);
+        assert_eq!(alice, auth.check(&req, &mut response).unwrap());
+
+        let req = request("/chat?token=s3cret", &[]);
+        assert_eq!(alice, auth.check(&req, &mut response).unwrap());
+        assert!(response.headers().get(SEC_WEBSOCKET_PROTOCOL).is_none());
+
+        let req = request("/", &[("Sec-WebSocket-Protocol", "bearer.s3cret, chat")]);
+        assert_eq!(alice, auth.check(&req, &mut response).unwrap());
+        assert_eq!("chat", response.headers()[SEC_WEBSOCKET_PROTOCOL]);
+
+        let rejected = auth.check(&request("/", &[]), &mut response).unwrap_err();
+        assert_eq!(StatusCode::UNAUTHORIZED, rejected.status());
+        let req = request("/?token=nope", &[]);
+        assert!(auth.check(&req, &mut response).is_err());
+
+        // 未配置认证方式时全部放行
+        assert_eq!(None, Auth::new().check(&req, &mut response).unwrap());
+    }
+
+    #[test]
+    fn verifies_hmac_tokens() {
+        let tokens = HmacTokens::new(b"key");
+        let future = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 60;
+
+        let token = tokens.sign("bob", future);
+        assert_eq!("bob", tokens.authenticate(&token).unwrap().subject);
+
+        // 过期、篡改或用其他密钥签名的令牌都会被拒绝
+        assert!(tokens.authenticate(&tokens.sign("bob", 1)).is_none());
+        let forged = token.replacen("bob", "eve", 1);
+        assert!(tokens.authenticate(&forged).is_none());
+        assert!(HmacTokens::new(b"other").authenticate(&token).is_none());
+        assert!(tokens.authenticate("garbage").is_none());
+    }
+}
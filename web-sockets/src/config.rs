@@ -0,0 +1,263 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::deflate::DeflateConfig;
+use crate::limit::Rate;
+
+// 命令行配置：web-sockets [ADDR] [--handler NAME] [--queue-size N]
+//                         [--tls-cert FILE --tls-key FILE] [--shutdown-timeout SECS]
+//                         [--ping-interval SECS] [--max-missed-pongs N] [--idle-timeout SECS]
+//                         [--auth-tokens FILE] [--auth-hmac-secret FILE]
+//                         [--deflate [--deflate-window-bits N] [--deflate-no-context-takeover]]
+//                         [--metrics-addr ADDR] [--max-connections N]
+//                         [--rate-messages N] [--rate-bytes N] [--ip-rate-messages N] [--ip-rate-bytes N]
+//       web-sockets --auth-hmac-secret FILE --issue-token SUBJECT [--token-ttl SECS]
+pub struct Config {
+    pub addr: SocketAddr,
+    pub handler: String,
+    // 每个连接发送队列的容量
+    pub queue_size: usize,
+    // PEM 格式的证书链和私钥，同时提供时启用 wss://
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    // 关闭时等待连接结束的最长时间
+    pub shutdown_timeout: Duration,
+    // 心跳 Ping 的间隔，连续多少次没有回应 Pong 就断开
+    pub ping_interval: Duration,
+    pub max_missed_pongs: usize,
+    // 多久没有收到文本或二进制消息就关闭连接；握手也必须在这段时间内完成，
+    // 半开的、从不完成握手的连接同样会被清理
+    pub idle_timeout: Duration,
+    // 静态令牌文件和 HMAC 密钥文件，提供任意一个即启用认证
+    pub auth_tokens: Option<PathBuf>,
+    pub auth_hmac_secret: Option<PathBuf>,
+    // 只签发一个 HMAC 令牌并输出，不启动服务器
+    pub issue_token: Option<String>,
+    pub token_ttl: Duration,
+    // 启用 permessage-deflate 压缩，客户端在握手中请求时才会使用
+    pub deflate: Option<DeflateConfig>,
+    // 提供 Prometheus 指标（GET /metrics）的 HTTP 地址，不提供时不启用
+    pub metrics_addr: Option<SocketAddr>,
+    // 同时打开的连接数上限，超过时新连接在握手时收到 HTTP 503
+    pub max_connections: Option<usize>,
+    // 每个连接和每个 IP 每秒允许收到的消息数和字节数，超过时以 1008 关闭连接
+    pub connection_rate: Rate,
+    pub ip_rate: Rate,
+}
+
+impl Config {
+    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
+        // 跳过程序名
+        args.next();
+
+        let mut addr = None;
+        let mut handler = String::from("reverse");
+        let mut queue_size = 64;
+        let mut tls_cert = None;
+        let mut tls_key = None;
+        let mut shutdown_timeout = Duration::from_secs(10);
+        let mut ping_interval = Duration::from_secs(30);
+        let mut max_missed_pongs = 2;
+        let mut idle_timeout = Duration::from_secs(300);
+        let mut auth_tokens = None;
+        let mut auth_hmac_secret = None;
+        let mut issue_token = None;
+        let mut token_ttl = Duration::from_secs(3600);
+        let mut deflate = false;
+        let mut deflate_window_bits = 15;
+        let mut deflate_no_context_takeover = false;
+        let mut metrics_addr = None;
+        let mut max_connections = None;
+        let mut connection_rate = Rate::default();
+        let mut ip_rate = Rate::default();
+
+        while let Some(arg) = args.next() {
+            // 同时支持 --name value 与 --name=value 两种写法
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if arg.starts_with("--") => (name.to_string(), Some(value)),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || match inline {
+                Some(value) => Ok(value.to_string()),
+                None => args
+                    .next()
+                    .ok_or_else(|| format!("{} requires a value", name)),
+            };
+
+            match name.as_str() {
+                "--handler" => handler = value()?,
+                "--queue-size" => queue_size = parse_number(&name, &value()?)?,
+                "--tls-cert" => tls_cert = Some(PathBuf::from(value()?)),
+                "--tls-key" => tls_key = Some(PathBuf::from(value()?)),
+                "--shutdown-timeout" => shutdown_timeout = parse_seconds(&name, &value()?)?,
+                "--ping-interval" => ping_interval = parse_seconds(&name, &value()?)?,
+                "--max-missed-pongs" => max_missed_pongs = parse_number(&name, &value()?)?,
+                "--idle-timeout" => idle_timeout = parse_seconds(&name, &value()?)?,
+                "--auth-tokens" => auth_tokens = Some(PathBuf::from(value()?)),
+                "--auth-hmac-secret" => auth_hmac_secret = Some(PathBuf::from(value()?)),
+                "--issue-token" => issue_token = Some(value()?),
+                "--token-ttl" => token_ttl = parse_seconds(&name, &value()?)?,
+                "--deflate" => deflate = true,
+                "--deflate-window-bits" => {
+                    deflate_window_bits = match value()?.parse() {
+                        Ok(bits @ 9..=15) => bits,
+                        _ => return Err(format!("{} must be between 9 and 15", name)),
+                    }
+                }
+                "--deflate-no-context-takeover" => deflate_no_context_takeover = true,
+                "--max-connections" => max_connections = Some(parse_number(&name, &value()?)?),
+                "--rate-messages" => {
+                    connection_rate.messages = Some(parse_number(&name, &value()?)?)
+                }
+                "--rate-bytes" => connection_rate.bytes = Some(parse_number(&name, &value()?)?),
+                "--ip-rate-messages" => ip_rate.messages = Some(parse_number(&name, &value()?)?),
+                "--ip-rate-bytes" => ip_rate.bytes = Some(parse_number(&name, &value()?)?),
+                "--metrics-addr" => {
+                    let value = value()?;
+                    metrics_addr = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid address {}", value))?,
+                    );
+                }
+                _ if name.starts_with("--") => return Err(format!("unknown option {}", name)),
+                _ if addr.is_none() => addr = Some(arg),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        if tls_cert.is_some() != tls_key.is_some() {
+            return Err("--tls-cert and --tls-key must be used together".to_string());
+        }
+
+        if issue_token.is_some() && auth_hmac_secret.is_none() {
+            return Err("--issue-token requires --auth-hmac-secret".to_string());
+        }
+
+        // 没有提供地址时默认使用 127.0.0.1:8080
+        let addr = addr.unwrap_or_else(|| "127.0.0.1:8080".to_string());
+        let addr = addr
+            .parse()
+            .map_err(|_| format!("invalid address {}", addr))?;
+
+        Ok(Config {
+            addr,
+            handler,
+            queue_size,
+            tls_cert,
+            tls_key,
+            shutdown_timeout,
+            ping_interval,
+            max_missed_pongs,
+            idle_timeout,
+            auth_tokens,
+            auth_hmac_secret,
+            issue_token,
+            token_ttl,
+            deflate: deflate.then_some(DeflateConfig {
+                window_bits: deflate_window_bits,
+                no_context_takeover: deflate_no_context_takeover,
+            }),
+            metrics_addr,
+            max_connections,
+            connection_rate,
+            ip_rate,
+        })
+    }
+}
+
+fn parse_number(name: &str, value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("{} must be a positive integer", name)),
+    }
+}
+
+fn parse_seconds(name: &str, value: &str) -> Result<Duration, String> {
+    parse_number(name, value).map(|secs| Duration::from_secs(secs as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(args: &[&str]) -> Result<Config, String> {
+        Config::build(
+            std::iter::once("web-sockets")
+                .chain(args.iter().copied())
+                .map(String::from),
+        )
+    }
+
+    #[test]
+    fn parses_address_and_options() {
+        let config = build(&[]).unwrap();
+        assert_eq!("127.0.0.1:8080", config.addr.to_string());
+        assert_eq!("reverse", config.handler);
+        assert_eq!(64, config.queue_size);
+        assert_eq!(Duration::from_secs(10), config.shutdown_timeout);
+        assert_eq!(Duration::from_secs(30), config.ping_interval);
+        assert_eq!(2, config.max_missed_pongs);
+
+        let config = build(&["0.0.0.0:9000", "--handler=echo"]).unwrap();
+        assert_eq!("0.0.0.0:9000", config.addr.to_string());
+        assert_eq!("echo", config.handler);
+
+        let config = build(&["--queue-size", "8", "--shutdown-timeout=3"]).unwrap();
+        assert_eq!(8, config.queue_size);
+        assert_eq!(Duration::from_secs(3), config.shutdown_timeout);
+
+        let config = build(&[
+            "--ping-interval=5",
+            "--max-missed-pongs=3",
+            "--idle-timeout=60",
+        ])
+        .unwrap();
+        assert_eq!(Duration::from_secs(5), config.ping_interval);
+        assert_eq!(3, config.max_missed_pongs);
+        assert_eq!(Duration::from_secs(60), config.idle_timeout);
+        assert_eq!(None, config.auth_tokens);
+
+        let config = build(&["--auth-tokens", "tokens.txt", "--auth-hmac-secret=key"]).unwrap();
+        assert_eq!(Some(PathBuf::from("tokens.txt")), config.auth_tokens);
+        assert_eq!(Some(PathBuf::from("key")), config.auth_hmac_secret);
+        assert!(build(&["--issue-token", "alice"]).is_err());
+
+        assert!(config.deflate.is_none());
+        let config = build(&["--deflate", "--deflate-window-bits=10"]).unwrap();
+        let deflate = config.deflate.unwrap();
+        assert_eq!(10, deflate.window_bits);
+        assert!(!deflate.no_context_takeover);
+        assert!(build(&["--deflate-window-bits", "8"]).is_err());
+
+        assert_eq!(None, config.metrics_addr);
+        let config = build(&["--metrics-addr=127.0.0.1:9100"]).unwrap();
+        assert_eq!("127.0.0.1:9100", config.metrics_addr.unwrap().to_string());
+        assert!(build(&["--metrics-addr", "nope"]).is_err());
+
+        assert_eq!(None, config.max_connections);
+        let config = build(&[
+            "--max-connections=100",
+            "--rate-messages=20",
+            "--ip-rate-bytes",
+            "65536",
+        ])
+        .unwrap();
+        assert_eq!(Some(100), config.max_connections);
+        assert_eq!(Some(20), config.connection_rate.messages);
+        assert_eq!(None, config.connection_rate.bytes);
+        assert_eq!(Some(65536), config.ip_rate.bytes);
+        assert!(build(&["--rate-bytes=0"]).is_err());
+
+        let config = build(&["--tls-cert", "cert.pem", "--tls-key=key.pem"]).unwrap();
+        assert_eq!(Some(PathBuf::from("cert.pem")), config.tls_cert);
+        assert_eq!(Some(PathBuf::from("key.pem")), config.tls_key);
+
+        assert!(build(&["--handler"]).is_err());
+        assert!(build(&["--tls-cert", "cert.pem"]).is_err());
+        assert!(build(&["--queue-size=0"]).is_err());
+        assert!(build(&["--nope"]).is_err());
+        assert!(build(&["localhost"]).is_err());
+    }
+}
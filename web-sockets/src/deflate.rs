@@ -0,0 +1,766 @@
+// 错误类型沿用 tungstenite 的 Error，与 WebSocketStream 保持一致
+#![allow(clippy::result_large_err)]
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use futures::{Sink, Stream};
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::tungstenite::error::{CapacityError, ProtocolError};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::{CloseCode, Control, Data, OpCode};
+use tokio_tungstenite::tungstenite::protocol::frame::{CloseFrame, FrameHeader};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+// permessage-deflate 扩展（RFC 7692）。
+// tungstenite 不支持扩展，遇到 RSV1 置位的帧会直接报错，所以协商成功的连接改用这里的
+// DeflateStream：自己读写帧，对文本和二进制消息做压缩和解压，对外和 WebSocketStream 一样
+// 是 Message 的 Stream + Sink。
+
+// 与 tungstenite 默认的消息大小上限一致，解压时同样检查，防止压缩炸弹
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+// 太短的消息压缩后反而可能变长，直接发送
+const MIN_COMPRESS_SIZE: usize = 64;
+
+const TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+// 服务器端的配置
+#[derive(Clone, Copy)]
+pub struct DeflateConfig {
+    // 服务器压缩使用的窗口大小（9..=15），越小越省内存，压缩率越低
+    pub window_bits: u8,
+    // 每条消息使用独立的压缩上下文，双向都不保留，节省每个连接的内存
+    pub no_context_takeover: bool,
+}
+
+// 协商结果
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Params {
+    pub server_no_context_takeover: bool,
+    pub client_no_context_takeover: bool,
+    pub server_max_window_bits: u8,
+}
+
+// 从 Sec-WebSocket-Extensions 中选择第一个可以接受的 permessage-deflate 提议，
+// 返回协商结果和响应头的值
+pub fn negotiate(config: &DeflateConfig, offers: &str) -> Option<(Params, String)> {
+    offers.split(',').find_map(|offer| {
+        let mut parts = offer.split(';').map(str::trim);
+        if parts.next()? != "permessage-deflate" {
+            return None;
+        }
+
+        let mut params = Params {
+            server_no_context_takeover: config.no_context_takeover,
+            client_no_context_takeover: config.no_context_takeover,
+            server_max_window_bits: config.window_bits,
+        };
+        let mut seen = Vec::new();
+        for part in parts {
+            let (name, value) = match part.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (part, None),
+            };
+            // 参数重复的提议无效
+            if seen.contains(&name) {
+                return None;
+            }
+            seen.push(name);
+
+            match (name, value) {
+                ("server_no_context_takeover", None) => params.server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => params.client_no_context_takeover = true,
+                ("server_max_window_bits", Some(bits)) => {
+                    let bits: u8 = bits.parse().ok()?;
+                    // zlib 不支持 8 位的原始 deflate 窗口，只能拒绝这个提议
+                    if !(9..=15).contains(&bits) {
+                        return None;
+                    }
+                    params.server_max_window_bits = params.server_max_window_bits.min(bits);
+                }
+                // 解压总是使用最大的窗口，可以处理客户端的任何窗口大小，不需要回应
+                ("client_max_window_bits", None) => (),
+                ("client_max_window_bits", Some(bits)) => {
+                    let bits: u8 = bits.parse().ok()?;
+                    if !(8..=15).contains(&bits) {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
+        }
+
+        let mut response = String::from("permessage-deflate");
+        if params.server_no_context_takeover {
+            response.push_str("; server_no_context_takeover");
+        }
+        if params.client_no_context_takeover {
+            response.push_str("; client_no_context_takeover");
+        }
+        if params.server_max_window_bits < 15 {
+            response.push_str(&format!(
+                "; server_max_window_bits={}",
+                params.server_max_window_bits
+            ));
+        }
+        Some((params, response))
+    })
+}
+
+fn invalid_data(e: impl std::error::Error + Send + Sync + 'static) -> WsError {
+    WsError::Io(io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn too_long(size: usize) -> WsError {
+    WsError::Capacity(CapacityError::MessageTooLong {
+        size,
+        max_size: MAX_MESSAGE_SIZE,
+    })
+}
+
+pub struct DeflateStream<S> {
+    io: S,
+    params: Params,
+    compress: Compress,
+    decompress: Decompress,
+    // 已读取但还没有解析的字节，以及等待写出的字节
+    read_buf: Vec<u8>,
+    write_buf: Vec<u8>,
+    // 正在接收的分片消息：类型、是否压缩、已收到的负载
+    fragments: Option<(Data, bool, Vec<u8>)>,
+    close_sent: bool,
+    close_received: bool,
+    // 发送一侧最近一次等待时的唤醒器，见 poll_write_from_reader
+    write_waker: Option<Waker>,
+}
+
+// 同时唤醒读取任务和发送任务
+struct WakeBoth {
+    read: Waker,
+    write: Option<Waker>,
+}
+
+impl Wake for WakeBoth {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.read.wake_by_ref();
+        if let Some(write) = &self.write {
+            write.wake_by_ref();
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> DeflateStream<S> {
+    pub fn new(io: S, params: Params) -> DeflateStream<S> {
+        DeflateStream {
+            io,
+            params,
+            compress: Compress::new_with_window_bits(
+                Compression::default(),
+                false,
+                params.server_max_window_bits,
+            ),
+            decompress: Decompress::new_with_window_bits(false, 15),
+            read_buf: Vec::new(),
+            write_buf: Vec::new(),
+            fragments: None,
+            close_sent: false,
+            close_received: false,
+            write_waker: None,
+        }
+    }
+
+    // 服务器发出的帧不加掩码
+    fn write_frame(&mut self, opcode: OpCode, compressed: bool, payload: &[u8]) {
+        let header = FrameHeader {
+            rsv1: compressed,
+            opcode,
+            ..FrameHeader::default()
+        };
+        header
+            .format(payload.len() as u64, &mut self.write_buf)
+            .expect("writing to a Vec cannot fail");
+        self.write_buf.extend_from_slice(payload);
+    }
+
+    fn write_close(&mut self, frame: Option<CloseFrame>) {
+        let mut payload = Vec::new();
+        if let Some(frame) = frame {
+            payload.extend_from_slice(&u16::from(frame.code).to_be_bytes());
+            payload.extend_from_slice(frame.reason.as_bytes());
+        }
+        self.write_frame(OpCode::Control(Control::Close), false, &payload);
+        self.close_sent = true;
+    }
+
+    fn deflate(&mut self, data: &[u8]) -> Result<Vec<u8>, WsError> {
+        let mut out = Vec::with_capacity(data.len() / 2 + 64);
+        let mut input = data;
+        loop {
+            let before = self.compress.total_in();
+            self.compress
+                .compress_vec(input, &mut out, FlushCompress::Sync)
+                .map_err(invalid_data)?;
+            input = &input[(self.compress.total_in() - before) as usize..];
+            // 输入已经用完且输出缓冲区还有空间，说明刷新已经完成
+            if input.is_empty() && out.len() < out.capacity() {
+                break;
+            }
+            out.reserve(out.capacity().max(64));
+        }
+
+        // 同步刷新以 00 00 ff ff 结尾，按规范去掉，接收方解压前再补上
+        if out.ends_with(&TRAILER) {
+            out.truncate(out.len() - TRAILER.len());
+        }
+        if self.params.server_no_context_takeover {
+            self.compress.reset();
+        }
+        Ok(out)
+    }
+
+    fn inflate(&mut self, mut data: Vec<u8>) -> Result<Vec<u8>, WsError> {
+        data.extend_from_slice(&TRAILER);
+        let mut out = Vec::with_capacity(data.len() * 2);
+        let mut input = &data[..];
+        let mut ended = false;
+        loop {
+            let (before_in, before_out) = (self.decompress.total_in(), self.decompress.total_out());
+            let status = self
+                .decompress
+                .decompress_vec(input, &mut out, FlushDecompress::Sync)
+                .map_err(invalid_data)?;
+            let consumed = (self.decompress.total_in() - before_in) as usize;
+            let produced = self.decompress.total_out() - before_out;
+            input = &input[consumed..];
+            if out.len() > MAX_MESSAGE_SIZE {
+                return Err(too_long(out.len()));
+            }
+            // 客户端可以用 BFINAL 结束最后一个块，压缩流到此结束，补上的 00 00 ff ff 不会被消费
+            if status == Status::StreamEnd {
+                ended = true;
+                break;
+            }
+            if input.is_empty() && out.len() < out.capacity() {
+                break;
+            }
+            // 输出缓冲区还有空间却没有任何进展，说明数据有问题，不能一直扩大缓冲区
+            if consumed == 0 && produced == 0 && out.len() < out.capacity() {
+                return Err(invalid_data(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "deflate stream made no progress",
+                )));
+            }
+            out.reserve(out.capacity().max(64).min(MAX_MESSAGE_SIZE + 1 - out.len()));
+        }
+
+        // 压缩流结束后，下一条消息从新的流开始，无论是否保留上下文
+        if ended || self.params.client_no_context_takeover {
+            self.decompress.reset(false);
+        }
+        Ok(out)
+    }
+
+    // 从缓冲区中解析一个完整的帧，数据不足时返回 None
+    fn parse_frame(&mut self) -> Result<Option<(FrameHeader, Vec<u8>)>, WsError> {
+        let mut cursor = Cursor::new(&self.read_buf);
+        let (header, len) = match FrameHeader::parse(&mut cursor)? {
+            Some(parsed) => parsed,
+            None => return Ok(None),
+        };
+        let start = cursor.position() as usize;
+        if len > MAX_MESSAGE_SIZE as u64 {
+            return Err(too_long(len as usize));
+        }
+        let end = start + len as usize;
+        if self.read_buf.len() < end {
+            return Ok(None);
+        }
+
+        let mut payload = self.read_buf[start..end].to_vec();
+        self.read_buf.drain(..end);
+
+        // 客户端发出的帧必须带掩码
+        let mask = header
+            .mask
+            .ok_or(WsError::Protocol(ProtocolError::UnmaskedFrameFromClient))?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        Ok(Some((header, payload)))
+    }
+
+    // 处理一个帧，组成完整的消息时返回消息
+    fn handle_frame(
+        &mut self,
+        header: FrameHeader,
+        payload: Vec<u8>,
+    ) -> Result<Option<Message>, WsError> {
+        // RSV1 只能出现在数据消息的第一个帧上，表示整条消息是压缩过的
+        let first_data = matches!(header.opcode, OpCode::Data(Data::Text | Data::Binary));
+        if header.rsv2 || header.rsv3 || (header.rsv1 && !first_data) {
+            return Err(WsError::Protocol(ProtocolError::NonZeroReservedBits));
+        }
+
+        match header.opcode {
+            OpCode::Control(control) => {
+                if !header.is_final {
+                    return Err(WsError::Protocol(ProtocolError::FragmentedControlFrame));
+                }
+                if payload.len() > 125 {
+                    return Err(WsError::Protocol(ProtocolError::ControlFrameTooBig));
+                }
+
+                match control {
+                    Control::Ping => {
+                        // 与 tungstenite 一样自动回应 Pong
+                        if !self.close_sent {
+                            self.write_frame(OpCode::Control(Control::Pong), false, &payload);
+                        }
+                        Ok(Some(Message::Ping(payload)))
+                    }
+                    Control::Pong => Ok(Some(Message::Pong(payload))),
+                    Control::Close => {
+                        let frame = match payload.len() {
+                            0 | 1 => None,
+                            _ => Some(CloseFrame {
+                                code: CloseCode::from(u16::from_be_bytes([payload[0], payload[1]])),
+                                reason: String::from_utf8(payload[2..].to_vec())?.into(),
+                            }),
+                        };
+                        // 对端先发起关闭时原样回应
+                        if !self.close_sent {
+                            self.write_close(frame.clone());
+                        }
+                        self.close_received = true;
+                        Ok(Some(Message::Close(frame)))
+                    }
+                    Control::Reserved(i) => {
+                        Err(WsError::Protocol(ProtocolError::UnknownControlFrameType(i)))
+                    }
+                }
+            }
+            OpCode::Data(Data::Continue) => {
+                let (_, _, data) = self
+                    .fragments
+                    .as_mut()
+                    .ok_or(WsError::Protocol(ProtocolError::UnexpectedContinueFrame))?;
+                if data.len() + payload.len() > MAX_MESSAGE_SIZE {
+                    return Err(too_long(data.len() + payload.len()));
+                }
+                data.extend_from_slice(&payload);
+                self.finish_message(header.is_final)
+            }
+            OpCode::Data(data @ (Data::Text | Data::Binary)) => {
+                if let Some((kind, _, _)) = self.fragments {
+                    return Err(WsError::Protocol(ProtocolError::ExpectedFragment(kind)));
+                }
+                self.fragments = Some((data, header.rsv1, payload));
+                self.finish_message(header.is_final)
+            }
+            OpCode::Data(Data::Reserved(i)) => {
+                Err(WsError::Protocol(ProtocolError::UnknownDataFrameType(i)))
+            }
+        }
+    }
+
+    fn finish_message(&mut self, is_final: bool) -> Result<Option<Message>, WsError> {
+        if !is_final {
+            return Ok(None);
+        }
+
+        let (kind, compressed, data) = self.fragments.take().expect("message in progress");
+        let data = if compressed {
+            self.inflate(data)?
+        } else {
+            data
+        };
+        match kind {
+            Data::Text => Ok(Some(Message::Text(String::from_utf8(data)?))),
+            _ => Ok(Some(Message::Binary(data))),
+        }
+    }
+
+    // 尽量写出缓冲区中的数据
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), WsError>> {
+        while !self.write_buf.is_empty() {
+            let n = match Pin::new(&mut self.io).poll_write(cx, &self.write_buf) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()))
+                }
+                Poll::Ready(Ok(n)) => n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => return Poll::Pending,
+            };
+            self.write_buf.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    // 读取时顺带写出自动回应的 Pong 和关闭帧，写不动时留到下次。
+    // 底层的流只保存一个写唤醒器，读取任务写入时会替换掉发送任务的唤醒器，
+    // 所以这里用同时唤醒两个任务的唤醒器，并且写出数据后主动唤醒发送任务，
+    // 否则发送任务可能一直停在 poll_flush 上
+    fn poll_write_from_reader(&mut self, cx: &mut Context<'_>) -> Result<(), WsError> {
+        if self.write_buf.is_empty() {
+            return Ok(());
+        }
+
+        let waker = Waker::from(Arc::new(WakeBoth {
+            read: cx.waker().clone(),
+            write: self.write_waker.clone(),
+        }));
+        let mut both = Context::from_waker(&waker);
+        let before = self.write_buf.len();
+        let result = match self.poll_write_buf(&mut both) {
+            Poll::Ready(Ok(())) => match Pin::new(&mut self.io).poll_flush(&mut both) {
+                Poll::Ready(Err(e)) => Err(e.into()),
+                _ => Ok(()),
+            },
+            Poll::Ready(Err(e)) => Err(e),
+            Poll::Pending => Ok(()),
+        };
+        if self.write_buf.len() != before {
+            if let Some(write) = self.write_waker.take() {
+                write.wake();
+            }
+        }
+        result
+    }
+
+    // 发送一侧每次轮询时记录唤醒器
+    fn register_writer(&mut self, cx: &Context<'_>) {
+        match &self.write_waker {
+            Some(waker) if waker.will_wake(cx.waker()) => (),
+            _ => self.write_waker = Some(cx.waker().clone()),
+        }
+    }
+
+    fn poll_message(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Message>, WsError>> {
+        loop {
+            self.poll_write_from_reader(cx)?;
+
+            if let Some((header, payload)) = self.parse_frame()? {
+                if let Some(msg) = self.handle_frame(header, payload)? {
+                    return Poll::Ready(Ok(Some(msg)));
+                }
+                continue;
+            }
+            // 收到关闭帧后不再读取
+            if self.close_received {
+                return Poll::Ready(Ok(None));
+            }
+
+            let mut chunk = [0; 8192];
+            let mut buf = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut self.io).poll_read(cx, &mut buf) {
+                Poll::Ready(Ok(())) if buf.filled().is_empty() => {
+                    return Poll::Ready(Err(WsError::Protocol(
+                        ProtocolError::ResetWithoutClosingHandshake,
+                    )))
+                }
+                Poll::Ready(Ok(())) => self.read_buf.extend_from_slice(buf.filled()),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Stream for DeflateStream<S> {
+    type Item = Result<Message, WsError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_message(cx).map(Result::transpose)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Sink<Message> for DeflateStream<S> {
+    type Error = WsError;
+
+    // 积压太多时先写出一部分，起到反压的作用
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), WsError>> {
+        let this = self.get_mut();
+        this.register_writer(cx);
+        if this.write_buf.len() > MAX_MESSAGE_SIZE {
+            return this.poll_write_buf(cx);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Message) -> Result<(), WsError> {
+        let this = self.get_mut();
+        if this.close_sent {
+            return Err(WsError::Protocol(ProtocolError::SendAfterClosing));
+        }
+
+        match msg {
+            Message::Text(text) => this.send_data(Data::Text, text.as_bytes())?,
+            Message::Binary(data) => this.send_data(Data::Binary, &data)?,
+            Message::Ping(data) => this.write_frame(OpCode::Control(Control::Ping), false, &data),
+            Message::Pong(data) => this.write_frame(OpCode::Control(Control::Pong), false, &data),
+            Message::Close(frame) => this.write_close(frame),
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), WsError>> {
+        let this = self.get_mut();
+        this.register_writer(cx);
+        futures::ready!(this.poll_write_buf(cx))?;
+        Pin::new(&mut this.io).poll_flush(cx).map_err(WsError::Io)
+    }
+
+    // 还没有发送关闭帧时先发送，然后关闭底层连接（TLS 连接会发送 close_notify）
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), WsError>> {
+        let this = self.get_mut();
+        this.register_writer(cx);
+        if !this.close_sent {
+            this.write_close(None);
+        }
+        futures::ready!(this.poll_write_buf(cx))?;
+        Pin::new(&mut this.io)
+            .poll_shutdown(cx)
+            .map_err(WsError::Io)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> DeflateStream<S> {
+    fn send_data(&mut self, kind: Data, data: &[u8]) -> Result<(), WsError> {
+        if data.len() < MIN_COMPRESS_SIZE {
+            self.write_frame(OpCode::Data(kind), false, data);
+        } else {
+            let compressed = self.deflate(data)?;
+            self.write_frame(OpCode::Data(kind), true, &compressed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    const CONFIG: DeflateConfig = DeflateConfig {
+        window_bits: 15,
+        no_context_takeover: false,
+    };
+
+    #[test]
+    fn negotiates_parameters() {
+        let (params, response) =
+            negotiate(&CONFIG, "permessage-deflate; client_max_window_bits").unwrap();
+        assert_eq!("permessage-deflate", response);
+        assert!(!params.server_no_context_takeover);
+
+        let (params, response) = negotiate(
+            &CONFIG,
+            "x-webkit-deflate-frame, permessage-deflate; server_max_window_bits=10; client_no_context_takeover",
+        )
+        .unwrap();
+        assert_eq!(10, params.server_max_window_bits);
+        assert!(params.client_no_context_takeover);
+        assert_eq!(
+            "permessage-deflate; client_no_context_takeover; server_max_window_bits=10",
+            response
+        );
+
+        // 不支持 8 位窗口时退回到下一个提议
+        let small = DeflateConfig {
+            window_bits: 12,
+            no_context_takeover: true,
+        };
+        let (params, response) = negotiate(
+            &small,
+            "permessage-deflate; server_max_window_bits=8, permessage-deflate",
+        )
+        .unwrap();
+        assert_eq!(12, params.server_max_window_bits);
+        assert_eq!(
+            "permessage-deflate; server_no_context_takeover; client_no_context_takeover; server_max_window_bits=12",
+            response
+        );
+
+        assert!(negotiate(&CONFIG, "permessage-deflate; unknown").is_none());
+        assert!(negotiate(
+            &CONFIG,
+            "permessage-deflate; server_no_context_takeover; server_no_context_takeover"
+        )
+        .is_none());
+        assert!(negotiate(&CONFIG, "x-webkit-deflate-frame").is_none());
+    }
+
+    // 按客户端的方式写一个带掩码的帧
+    fn client_frame(opcode: OpCode, rsv1: bool, is_final: bool, payload: &[u8]) -> Vec<u8> {
+        let mask = [1, 2, 3, 4];
+        let header = FrameHeader {
+            is_final,
+            rsv1,
+            opcode,
+            mask: Some(mask),
+            ..FrameHeader::default()
+        };
+        let mut frame = Vec::new();
+        header.format(payload.len() as u64, &mut frame).unwrap();
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut compress = Compress::new(Compression::default(), false);
+        let mut out = Vec::with_capacity(data.len() + 64);
+        compress
+            .compress_vec(data, &mut out, FlushCompress::Sync)
+            .unwrap();
+        out.truncate(out.len() - TRAILER.len());
+        out
+    }
+
+    #[tokio::test]
+    async fn round_trips_compressed_messages() {
+        let (server, mut client) = duplex(1 << 16);
+        let params = negotiate(&CONFIG, "permessage-deflate").unwrap().0;
+        let mut ws = DeflateStream::new(server, params);
+
+        // 压缩后分成两个分片发送，中间插入一个 Ping
+        let text = "hello ".repeat(50);
+        let compressed = compress(text.as_bytes());
+        let (first, rest) = compressed.split_at(5);
+        client
+            .write_all(&client_frame(OpCode::Data(Data::Text), true, false, first))
+            .await
+            .unwrap();
+        client
+            .write_all(&client_frame(
+                OpCode::Control(Control::Ping),
+                false,
+                true,
+                b"hb",
+            ))
+            .await
+            .unwrap();
+        client
+            .write_all(&client_frame(
+                OpCode::Data(Data::Continue),
+                false,
+                true,
+                rest,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Message::Ping(b"hb".to_vec()),
+            ws.next().await.unwrap().unwrap()
+        );
+        assert_eq!(
+            Message::Text(text.clone()),
+            ws.next().await.unwrap().unwrap()
+        );
+
+        // 服务器的回复是压缩过的，能用普通的 raw deflate 解压
+        ws.send(Message::Text(text.clone())).await.unwrap();
+        let mut received = vec![0; 4096];
+        let n = client.read(&mut received).await.unwrap();
+        received.truncate(n);
+
+        let pong = [0x8a, 2, b'h', b'b'];
+        assert_eq!(pong, received[..4]);
+        let reply = &received[4..];
+        assert_eq!(0x80 | 0x40 | 0x1, reply[0]);
+        let mut payload = reply[2..].to_vec();
+        assert_eq!(reply[1] as usize, payload.len());
+        payload.extend_from_slice(&TRAILER);
+        let mut decompress = Decompress::new(false);
+        let mut out = Vec::with_capacity(text.len() + 16);
+        decompress
+            .decompress_vec(&payload, &mut out, FlushDecompress::Sync)
+            .unwrap();
+        assert_eq!(text.as_bytes(), &out[..]);
+    }
+
+    #[tokio::test]
+    async fn accepts_final_deflate_blocks() {
+        let (server, mut client) = duplex(1 << 16);
+        let params = negotiate(&CONFIG, "permessage-deflate").unwrap().0;
+        let mut ws = DeflateStream::new(server, params);
+
+        // 以 BFINAL 结束的压缩数据，之后的消息从新的压缩流开始
+        for text in ["first ".repeat(20), "second ".repeat(20)] {
+            let mut compress = Compress::new(Compression::default(), false);
+            let mut finished = Vec::with_capacity(text.len() + 64);
+            compress
+                .compress_vec(text.as_bytes(), &mut finished, FlushCompress::Finish)
+                .unwrap();
+            let frame = client_frame(OpCode::Data(Data::Text), true, true, &finished);
+            client.write_all(&frame).await.unwrap();
+            assert_eq!(Message::Text(text), ws.next().await.unwrap().unwrap());
+        }
+    }
+
+    // 发送任务阻塞在写入上时，读取任务写出自动回应的 Pong 不能让发送任务失去唤醒
+    #[tokio::test]
+    async fn reader_writes_do_not_strand_the_writer() {
+        let (server, client) = duplex(256);
+        let (mut client_read, mut client_write) = tokio::io::split(client);
+        let params = negotiate(&CONFIG, "permessage-deflate").unwrap().0;
+        let (mut sink, mut stream) = DeflateStream::new(server, params).split();
+
+        // 不可压缩的数据，远大于管道的容量
+        let mut seed = 1u32;
+        let data: Vec<u8> = (0..8192)
+            .map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (seed >> 24) as u8
+            })
+            .collect();
+        let writer = tokio::spawn(async move { sink.send(Message::Binary(data)).await });
+        tokio::task::yield_now().await;
+
+        let ping = client_frame(OpCode::Control(Control::Ping), false, true, b"hb");
+        client_write.write_all(&ping).await.unwrap();
+        let reader = tokio::spawn(async move { while let Some(Ok(_)) = stream.next().await {} });
+        tokio::task::yield_now().await;
+
+        let drain = tokio::spawn(async move {
+            let mut buf = [0; 1024];
+            while client_read.read(&mut buf).await.unwrap() > 0 {}
+        });
+        tokio::time::timeout(std::time::Duration::from_secs(5), writer)
+            .await
+            .expect("writer was never woken")
+            .unwrap()
+            .unwrap();
+        reader.abort();
+        drain.abort();
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_frames() {
+        let (server, mut client) = duplex(1 << 16);
+        let mut ws =
+            DeflateStream::new(server, negotiate(&CONFIG, "permessage-deflate").unwrap().0);
+
+        // 控制帧不允许设置 RSV1
+        client
+            .write_all(&client_frame(
+                OpCode::Control(Control::Ping),
+                true,
+                true,
+                b"",
+            ))
+            .await
+            .unwrap();
+        assert!(matches!(
+            ws.next().await,
+            Some(Err(WsError::Protocol(ProtocolError::NonZeroReservedBits)))
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::auth::Identity;
+use crate::rooms::Rooms;
+use futures::future::{self, BoxFuture};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+// 每个连接的上下文，随每条消息一起传给处理器
+pub struct ConnectionContext {
+    // 连接编号，进程内唯一，便于在日志中区分连接
+    pub id: u64,
+    pub peer: SocketAddr,
+    // 有界的发送队列，处理器可以随时向客户端推送消息（例如聊天室的广播）
+    pub outbound: mpsc::Sender<Message>,
+    // 升级请求的路径、路由参数（如 /chat/{room} 中的 room）和查询参数
+    pub path: String,
+    pub params: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+    // 启用认证时握手中校验通过的身份
+    pub identity: Option<Identity>,
+}
+
+impl ConnectionContext {
+    pub fn new(id: u64, peer: SocketAddr, outbound: mpsc::Sender<Message>) -> ConnectionContext {
+        ConnectionContext {
+            id,
+            peer,
+            outbound,
+            path: String::from("/"),
+            params: HashMap::new(),
+            query: HashMap::new(),
+            identity: None,
+        }
+    }
+}
+
+// 消息处理器：收到一条文本或二进制消息，返回零条或多条回复。
+// 返回 BoxFuture 而不是 async fn，这样 trait 可以作为 dyn 对象在运行时选择
+pub trait MessageHandler: Send + Sync + 'static {
+    fn handle<'a>(
+        &'a self,
+        ctx: &'a ConnectionContext,
+        msg: Message,
+    ) -> BoxFuture<'a, Vec<Message>>;
+
+    // 连接建立后、处理第一条消息之前调用
+    fn on_open(&self, _ctx: &ConnectionContext) {}
+
+    // 连接关闭时调用，用于清理处理器为该连接保存的状态
+    fn on_close(&self, _ctx: &ConnectionContext) {}
+}
+
+// 原样返回收到的消息
+pub struct Echo;
+
+impl MessageHandler for Echo {
+    fn handle<'a>(
+        &'a self,
+        _ctx: &'a ConnectionContext,
+        msg: Message,
+    ) -> BoxFuture<'a, Vec<Message>> {
+        Box::pin(future::ready(vec![msg]))
+    }
+}
+
+// 反转文本后返回，其他消息忽略
+pub struct Reverse;
+
+impl MessageHandler for Reverse {
+    fn handle<'a>(
+        &'a self,
+        _ctx: &'a ConnectionContext,
+        msg: Message,
+    ) -> BoxFuture<'a, Vec<Message>> {
+        let replies = match msg {
+            Message::Text(text) => vec![Message::Text(text.chars().rev().collect())],
+            _ => Vec::new(),
+        };
+        Box::pin(future::ready(replies))
+    }
+}
+
+pub const BUILTIN: [&str; 3] = ["reverse", "echo", "chat"];
+
+// 按名字选择内置处理器
+pub fn builtin(name: &str) -> Result<Arc<dyn MessageHandler>, String> {
+    match name {
+        "reverse" => Ok(Arc::new(Reverse)),
+        "echo" => Ok(Arc::new(Echo)),
+        "chat" => Ok(Arc::new(Rooms::new())),
+        _ => Err(format!(
+            "unknown handler {}, expected one of: {}",
+            name,
+            BUILTIN.join(", ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> ConnectionContext {
+        ConnectionContext::new(1, "127.0.0.1:9000".parse().unwrap(), mpsc::channel(1).0)
+    }
+
+    #[tokio::test]
+    async fn builtin_handlers() {
+        let ctx = ctx();
+
+        let reverse = builtin("reverse").unwrap();
+        assert_eq!(
+            vec![Message::Text("cba".into())],
+            reverse.handle(&ctx, Message::Text("abc".into())).await
+        );
+        assert!(reverse
+            .handle(&ctx, Message::Binary(vec![1, 2]))
+            .await
+            .is_empty());
+
+        let echo = builtin("echo").unwrap();
+        assert_eq!(
+            vec![Message::Binary(vec![1, 2])],
+            echo.handle(&ctx, Message::Binary(vec![1, 2])).await
+        );
+
+        assert!(builtin("nope").is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+// 令牌桶：每秒补充 rate 个令牌，最多积攒一秒的量，允许短时间的突发
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: usize, now: Instant) -> TokenBucket {
+        TokenBucket {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last: now,
+        }
+    }
+
+    // 令牌不足时不扣除；比一秒的量还大的请求永远无法通过
+    fn take(&mut self, n: usize, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last = now;
+        if self.tokens < n as f64 {
+            return false;
+        }
+        self.tokens -= n as f64;
+        true
+    }
+}
+
+// 每秒允许的消息数和字节数，None 表示不限制
+#[derive(Clone, Copy, Default)]
+pub struct Rate {
+    pub messages: Option<usize>,
+    pub bytes: Option<usize>,
+}
+
+struct Buckets {
+    messages: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+}
+
+impl Buckets {
+    fn new(rate: Rate, now: Instant) -> Buckets {
+        Buckets {
+            messages: rate.messages.map(|rate| TokenBucket::new(rate, now)),
+            bytes: rate.bytes.map(|rate| TokenBucket::new(rate, now)),
+        }
+    }
+
+    fn take(&mut self, len: usize, now: Instant) -> bool {
+        let messages = self.messages.as_mut().is_none_or(|b| b.take(1, now));
+        messages && self.bytes.as_mut().is_none_or(|b| b.take(len, now))
+    }
+}
+
+// 所有连接共享的限流状态。同一个 IP 的所有连接共享一组令牌桶，
+// 该 IP 的最后一个连接关闭时删除，表不会无限增长
+pub struct Limiter {
+    per_connection: Rate,
+    per_ip: Rate,
+    ips: Mutex<HashMap<IpAddr, (usize, Buckets)>>,
+}
+
+// 超过了哪一级的限制
+#[derive(Debug, PartialEq)]
+pub enum Exceeded {
+    Connection,
+    Ip,
+}
+
+impl Exceeded {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Exceeded::Connection => "connection",
+            Exceeded::Ip => "ip",
+        }
+    }
+}
+
+impl Limiter {
+    pub fn new(per_connection: Rate, per_ip: Rate) -> Limiter {
+        Limiter {
+            per_connection,
+            per_ip,
+            ips: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // 为一个新连接登记限流状态，返回的 ConnectionLimit 被丢弃时注销
+    pub fn connection(self: &Arc<Self>, ip: IpAddr) -> ConnectionLimit {
+        let now = Instant::now();
+        let mut ips = self.ips.lock().unwrap();
+        ips.entry(ip)
+            .or_insert_with(|| (0, Buckets::new(self.per_ip, now)))
+            .0 += 1;
+        ConnectionLimit {
+            limiter: self.clone(),
+            ip,
+            buckets: Buckets::new(self.per_connection, now),
+        }
+    }
+}
+
+pub struct ConnectionLimit {
+    limiter: Arc<Limiter>,
+    ip: IpAddr,
+    buckets: Buckets,
+}
+
+impl ConnectionLimit {
+    // 收到一条 len 字节的消息，先检查连接自己的限制，再检查所属 IP 的限制
+    pub fn check(&mut self, len: usize) -> Result<(), Exceeded> {
+        let now = Instant::now();
+        if !self.buckets.take(len, now) {
+            return Err(Exceeded::Connection);
+        }
+        let mut ips = self.limiter.ips.lock().unwrap();
+        let (_, buckets) = ips.get_mut(&self.ip).expect("registered in connection()");
+        if !buckets.take(len, now) {
+            return Err(Exceeded::Ip);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ConnectionLimit {
+    fn drop(&mut self) {
+        let mut ips = self.limiter.ips.lock().unwrap();
+        if let Some((count, _)) = ips.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                ips.remove(&self.ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(10, start);
+        assert!(bucket.take(10, start));
+        assert!(!bucket.take(1, start));
+
+        // 半秒补充 5 个令牌，但最多积攒一秒的量
+        assert!(bucket.take(5, start + Duration::from_millis(500)));
+        assert!(!bucket.take(1, start + Duration::from_millis(500)));
+        assert!(!bucket.take(11, start + Duration::from_secs(60)));
+        assert!(bucket.take(10, start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn limits_connections_and_ips() {
+        let per_connection = Rate {
+            messages: Some(3),
+            bytes: None,
+        };
+        let per_ip = Rate {
+            messages: None,
+            bytes: Some(100),
+        };
+        let limiter = Arc::new(Limiter::new(per_connection, per_ip));
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+
+        let mut first = limiter.connection(ip);
+        assert_eq!(Ok(()), first.check(10));
+        assert_eq!(Ok(()), first.check(10));
+        assert_eq!(Ok(()), first.check(10));
+        assert_eq!(Err(Exceeded::Connection), first.check(10));
+
+        // 同一 IP 的第二个连接有自己的消息数限制，但与第一个连接共享字节数限制
+        let mut second = limiter.connection(ip);
+        assert_eq!(Ok(()), second.check(60));
+        assert_eq!(Err(Exceeded::Ip), second.check(20));
+
+        // 其他 IP 不受影响
+        let mut other = limiter.connection("10.0.0.2".parse().unwrap());
+        assert_eq!(Ok(()), other.check(100));
+
+        drop(first);
+        drop(second);
+        drop(other);
+        assert!(limiter.ips.lock().unwrap().is_empty());
+    }
+}
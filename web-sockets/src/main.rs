@@ -1,16 +1,51 @@
-use futures::{SinkExt, StreamExt};
+mod auth;
+mod config;
+mod deflate;
+mod handler;
+mod limit;
+mod metrics;
+mod rooms;
+mod router;
+mod shutdown;
+mod tls;
+
+use auth::Identity;
+use auth::{Auth, HmacTokens, TokenFile};
+use config::Config;
+use deflate::DeflateStream;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use handler::{ConnectionContext, Echo, MessageHandler, Reverse};
+use limit::Limiter;
 use log::{error, info};
-use std::env;
+use metrics::Metrics;
+use rooms::Rooms;
+use router::{Matched, Router};
+use shutdown::{Drain, Watch};
 use std::net::SocketAddr;
-use tokio::net::{TcpListener, TcpStream};
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{env, process};
+use tls::Tls;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::{self, Instant};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::http::header::{HeaderValue, SEC_WEBSOCKET_EXTENSIONS};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tokio_tungstenite::{accept_hdr_async, WebSocketStream};
 
-// TcpListener 和 TcpStream 用于创建 TCP 监听器和连接。
-// accept_async 用于接受 WebSocket 连接。
+// TcpListener 用于创建 TCP 监听器，连接可以是普通的 TCP 流，也可以是其上的 TLS 流。
+// accept_hdr_async 用于接受 WebSocket 连接，握手回调按路径查找路由并校验令牌，
+// 没有匹配的路径时返回 HTTP 404，认证失败时返回 HTTP 401，都不升级连接。
 // Message 用于表示 WebSocket 消息。
 // StreamExt 和 SinkExt 用于处理异步流和发送数据。
 // env 用于访问环境变量，例如命令行参数。
-// SocketAddr 用于表示网络地址。
+// Config 用于解析命令行参数，MessageHandler 决定如何回复每条消息。
 // info 和 error 用于记录信息和错误消息。
 
 #[tokio::main]
@@ -18,70 +53,501 @@ async fn main() {
     // 初始化日志记录器
     env_logger::init();
 
-    // 获取要绑定的地址
-    let addr = env::args()
-        .nth(1)
-        .unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    // 解析命令行参数：地址和选项
+    let config = Config::build(env::args()).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {}", err);
+        process::exit(1);
+    });
+
+    // 按路径选择消息处理器，根路径使用 --handler 指定的处理器
+    let router = routes(&config.handler).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {}", err);
+        process::exit(1);
+    });
 
-    // 解析地址
-    let addr: SocketAddr = addr.parse().expect("invalid address");
+    // 配置了令牌文件或 HMAC 密钥时，握手必须携带有效的令牌
+    let mut auth = Auth::new();
+    if let Some(path) = &config.auth_tokens {
+        auth = auth.with(TokenFile::load(path).unwrap_or_else(|err| {
+            eprintln!("Problem loading auth tokens: {}", err);
+            process::exit(1);
+        }));
+    }
+    if let Some(path) = &config.auth_hmac_secret {
+        let tokens = HmacTokens::load(path).unwrap_or_else(|err| {
+            eprintln!("Problem loading auth tokens: {}", err);
+            process::exit(1);
+        });
+        // --issue-token 只输出签发的令牌
+        if let Some(subject) = &config.issue_token {
+            println!("{}", tokens.issue(subject, config.token_ttl));
+            return;
+        }
+        auth = auth.with(tokens);
+    }
+
+    // 提供了证书和私钥时以 wss:// 提供服务
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => {
+            let tls = Tls::load(cert.clone(), key.clone()).unwrap_or_else(|err| {
+                eprintln!("Problem loading TLS certificate: {}", err);
+                process::exit(1);
+            });
+            let tls = Arc::new(tls);
+            #[cfg(unix)]
+            tls.clone()
+                .reload_on_sighup()
+                .expect("failed to install SIGHUP handler");
+            Some(tls)
+        }
+        _ => None,
+    };
 
     // 创建 TCP 监听器
-    let listener = TcpListener::bind(&addr).await.expect("failed to bind");
+    let listener = TcpListener::bind(&config.addr)
+        .await
+        .expect("failed to bind");
 
-    info!("listening on: {}", addr);
+    info!(
+        "listening on: {}://{} (handler: {})",
+        if tls.is_some() { "wss" } else { "ws" },
+        config.addr,
+        config.handler
+    );
 
-    // 循环处理传入的连接
-    while let Ok((stream, _)) = listener.accept().await {
-        // 为每个连接启动一个新的任务
-        tokio::spawn(handle_connection(stream));
+    // 指标在独立的端口上提供，不经过 WebSocket 的路由和认证
+    let metrics = Arc::new(Metrics::new());
+    if let Some(addr) = config.metrics_addr {
+        let listener = TcpListener::bind(addr)
+            .await
+            .expect("failed to bind metrics address");
+        info!("serving metrics on: http://{}/metrics", addr);
+        tokio::spawn(metrics::serve(listener, metrics.clone()));
     }
 
+    let limiter = Arc::new(Limiter::new(config.connection_rate, config.ip_rate));
+    let server = Server {
+        config,
+        router,
+        auth,
+        metrics,
+        limiter,
+    };
+    serve(listener, Arc::new(server), tls).await;
+
     // 以上代码实现了以下功能：
     // 初始化日志记录器: 使用 env_logger::init() 初始化日志记录器，以便将信息和错误输出到控制台。
     // 获取地址: 从命令行参数获取要绑定的地址，如果没有提供，则默认使用 127.0.0.1:8080。
+    // 加载证书: 提供了 --tls-cert 和 --tls-key 时加载证书，并在收到 SIGHUP 时重新加载。
     // 创建 TCP 监听器: 使用 TcpListener::bind 创建一个 TCP 监听器，监听指定的地址。
     // 记录信息: 打印一条信息，表明服务器正在监听指定的地址。
+    // 提供指标: 提供了 --metrics-addr 时在该地址上以 HTTP 提供 Prometheus 指标。
     // 循环接受连接: 使用 listener.accept 循环接受来自客户端的连接，并将每个连接交给 handle_connection 函数处理。
+    // 连接数上限: 提供了 --max-connections 时，超过上限的新连接在握手时收到 HTTP 503。
     // 启动任务: 使用 tokio::spawn 为每个连接启动一个新的异步任务，以便同时处理多个连接。
 }
 
+// 内置的路由：/echo、/reverse、/chat 和 /chat/{room}，两个聊天路径共享同一组房间
+fn routes(default: &str) -> Result<Router, String> {
+    let rooms: Arc<dyn MessageHandler> = Arc::new(Rooms::new());
+    let default = match default {
+        "chat" => rooms.clone(),
+        name => handler::builtin(name)?,
+    };
+
+    Ok(Router::new()
+        .route("/", default)
+        .route("/echo", Arc::new(Echo))
+        .route("/reverse", Arc::new(Reverse))
+        .route("/chat", rooms.clone())
+        .route("/chat/{room}", rooms))
+}
+
+// 所有连接共享的服务器状态
+struct Server {
+    config: Config,
+    router: Router,
+    auth: Auth,
+    metrics: Arc<Metrics>,
+    limiter: Arc<Limiter>,
+}
+
+// 接受连接的循环。处理器是运行时选择的 Arc<dyn MessageHandler>，由 Server 中的 Router 按路径分派
+async fn serve(listener: TcpListener, server: Arc<Server>, tls: Option<Arc<Tls>>) {
+    let config = &server.config;
+    let next_id = AtomicU64::new(1);
+    let drain = Drain::new();
+    // 连接数上限：每个连接持有一个许可，任务结束时归还
+    let permits = config
+        .max_connections
+        .map(|max| Arc::new(Semaphore::new(max)));
+    let shutdown = shutdown::signal();
+    tokio::pin!(shutdown);
+
+    // 循环处理传入的连接，直到收到 SIGINT/SIGTERM
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                // 文件描述符耗尽等错误是暂时的，稍等后继续接受连接
+                Err(e) => {
+                    error!("error accepting connection: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        // 达到上限时不分配许可，仍然完成 HTTP 握手，以 503 拒绝升级，
+        // 客户端看到的是明确的拒绝而不是连接被重置
+        let permit = match &permits {
+            Some(permits) => match permits.clone().try_acquire_owned() {
+                Ok(permit) => Ok(Some(permit)),
+                Err(_) => {
+                    info!("too many connections, refusing {}", peer);
+                    server
+                        .metrics
+                        .limited
+                        .with_label_values(&["max_connections"])
+                        .inc();
+                    Err(())
+                }
+            },
+            None => Ok(None),
+        };
+        let admitted = permit.is_ok();
+
+        let id = next_id.fetch_add(1, Ordering::Relaxed);
+        let server = server.clone();
+        let watch = drain.watch();
+        // 每次都取当前的 acceptor，SIGHUP 重新加载后新连接立即使用新证书
+        let acceptor = tls.as_ref().map(|tls| tls.acceptor());
+
+        // 为每个连接启动一个新的任务，TLS 握手也在任务中进行，不阻塞接受新连接。
+        // TLS 和 WebSocket 握手共用 idle_timeout 的期限，迟迟不完成握手的连接会被断开并归还许可
+        tokio::spawn(async move {
+            let _permit = permit;
+            let deadline = Instant::now() + server.config.idle_timeout;
+            let handshake = Handshake {
+                id,
+                peer,
+                deadline,
+                admitted,
+            };
+            match acceptor {
+                Some(acceptor) => match time::timeout_at(deadline, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => handle_connection(stream, handshake, server, watch).await,
+                    Ok(Err(e)) => {
+                        server
+                            .metrics
+                            .handshakes
+                            .with_label_values(&["failed"])
+                            .inc();
+                        error!("error during the TLS handshake with {}: {}", peer, e)
+                    }
+                    Err(_) => handshake_timed_out(&server, peer),
+                },
+                None => handle_connection(stream, handshake, server, watch).await,
+            }
+        });
+    }
+
+    // 停止接受新连接，通知已有连接关闭并等待它们结束
+    drop(listener);
+    info!(
+        "shutting down, waiting up to {}s for connections to close",
+        config.shutdown_timeout.as_secs()
+    );
+    if !drain.drain(config.shutdown_timeout).await {
+        info!("shutdown timeout elapsed, closing remaining connections");
+    }
+}
+
+// 一个连接在握手阶段需要的信息
+struct Handshake {
+    id: u64,
+    peer: SocketAddr,
+    // TLS 和 WebSocket 握手必须在此之前完成
+    deadline: Instant,
+    // 是否拿到了连接数上限的许可，没有拿到时以 503 拒绝升级
+    admitted: bool,
+}
+
+fn handshake_timed_out(server: &Server, peer: SocketAddr) {
+    server
+        .metrics
+        .handshakes
+        .with_label_values(&["timeout"])
+        .inc();
+    info!("handshake with {} timed out, closing", peer);
+}
+
 // handle_connection 实现以下功能：
-// 接受 WebSocket 连接: 使用 accept_async 尝试从 TCP 流中接受一个 WebSocket 连接，如果成功，则返回一个 WebSocket 流。
+// 接受 WebSocket 连接: 使用 accept_hdr_async 在期限内从流中接受一个 WebSocket 连接，超时则直接断开。
+// 连接数上限: 没有拿到许可的连接在握手时返回 HTTP 503，不升级连接。
+// 路由: 握手时按请求路径选择处理器，没有匹配的路径时返回 HTTP 404，不升级连接。
+// 认证: 启用认证时校验令牌，失败时返回 HTTP 401，不升级连接。
+// 指标: 记录握手结果、连接数、收发的消息和字节数、发送错误以及处理器耗时。
+// 压缩: 启用 --deflate 且客户端请求时协商 permessage-deflate，之后由 DeflateStream 读写帧。
 // 拆分流: 将 WebSocket 流拆分为一个发送器和一个接收器，分别用于发送和接收消息。
+// 发送队列: 发送器由单独的任务从有界队列中取出消息发送，回复和处理器推送的消息都经过这个队列。
 // 处理消息: 循环接收来自客户端的消息，并根据消息类型进行不同的处理：
-// 文本消息:  反转接收到的文本内容，并将反转后的文本发送回客户端。
+// 文本和二进制消息:  交给处理器，并将处理器返回的回复依次发送回客户端。
 // 关闭消息:  结束连接。
 // 其他消息类型:  忽略。
 // 错误:  记录错误并结束连接。
-async fn handle_connection(stream: TcpStream) {
-    // 接受 WebSocket 连接
-    let ws_stream = match accept_async(stream).await {
-        Ok(ws) => ws,
+// 服务器关闭:  发送 1001 Going Away 关闭帧，不再处理新消息，等待客户端回应关闭帧后结束。
+// 心跳:  定期发送 Ping，连续多次收不到 Pong 时直接断开连接。
+// 空闲超时:  长时间没有收到消息时发送关闭帧；握手阶段的期限见 Handshake::deadline。
+// 限流:  连接或所属 IP 收到的消息数、字节数超过限制时发送 1008 关闭帧并结束连接。
+async fn handle_connection<S>(
+    mut stream: S,
+    handshake: Handshake,
+    server: Arc<Server>,
+    shutdown: Watch,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // 接受 WebSocket 连接，握手回调中记录匹配到的路由、认证后的身份和压缩参数
+    let mut matched = None;
+    let mut deflate = None;
+    // 错误类型由 tungstenite 的握手回调决定
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, mut response: Response| {
+        if !handshake.admitted {
+            return Err(router::reject(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "too many connections",
+            ));
+        }
+        let route = server
+            .router
+            .find(request)
+            .ok_or_else(|| router::reject(StatusCode::NOT_FOUND, "not found"))?;
+        let identity = server.auth.check(request, &mut response)?;
+        let offers = request
+            .headers()
+            .get_all(SEC_WEBSOCKET_EXTENSIONS)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        if let Some(config) = &server.config.deflate {
+            if let Some((params, accepted)) = deflate::negotiate(config, &offers) {
+                let accepted = HeaderValue::from_str(&accepted).expect("valid header value");
+                response
+                    .headers_mut()
+                    .insert(SEC_WEBSOCKET_EXTENSIONS, accepted);
+                deflate = Some(params);
+            }
+        }
+        matched = Some((route, identity));
+        Ok(response)
+    };
+    // 握手只借用底层的流，之后按是否启用压缩选择读写帧的实现
+    let Handshake { id, peer, .. } = handshake;
+    let result =
+        match time::timeout_at(handshake.deadline, accept_hdr_async(&mut stream, callback)).await {
+            Ok(result) => result,
+            Err(_) => return handshake_timed_out(&server, peer),
+        };
+    let handshakes = &server.metrics.handshakes;
+    match result {
+        Ok(_) => handshakes.with_label_values(&["accepted"]).inc(),
+        Err(WsError::Http(response)) => {
+            handshakes.with_label_values(&["rejected"]).inc();
+            info!("rejected upgrade from {}: {}", peer, response.status());
+            return;
+        }
         Err(e) => {
-            error!("error during the WebSocket handshake: {}", e);
+            handshakes.with_label_values(&["failed"]).inc();
+            error!("error during the WebSocket handshake with {}: {}", peer, e);
             return;
         }
     };
+    let (route, identity) = matched.expect("handshake succeeded without a route");
+
+    match deflate {
+        Some(params) => {
+            let ws = DeflateStream::new(stream, params);
+            run_connection(ws, id, peer, server, shutdown, route, identity).await
+        }
+        None => {
+            let ws = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+            run_connection(ws, id, peer, server, shutdown, route, identity).await
+        }
+    }
+}
+
+// 握手之后的消息循环，W 可以是 tungstenite 的 WebSocketStream，也可以是 DeflateStream
+async fn run_connection<W>(
+    ws_stream: W,
+    id: u64,
+    peer: SocketAddr,
+    server: Arc<Server>,
+    mut shutdown: Watch,
+    route: Matched,
+    identity: Option<Identity>,
+) where
+    W: Stream<Item = Result<Message, WsError>> + Sink<Message, Error = WsError> + Send + 'static,
+{
+    let config = &server.config;
+    let metrics = server.metrics.clone();
+    let handler = route.handler;
+    let mut limit = server.limiter.connection(peer.ip());
 
     let (mut sender, mut receiver) = ws_stream.split();
 
+    let (outbound, mut queue) = mpsc::channel(config.queue_size);
+    let mut ctx = ConnectionContext::new(id, peer, outbound);
+    ctx.path = route.path;
+    ctx.params = route.params;
+    ctx.query = route.query;
+    ctx.identity = identity;
+    match &ctx.identity {
+        Some(identity) => info!(
+            "[{}] {} connected from {} to {}",
+            ctx.id, identity.subject, ctx.peer, ctx.path
+        ),
+        None => info!("[{}] connected from {} to {}", ctx.id, ctx.peer, ctx.path),
+    }
+    handler.on_open(&ctx);
+    metrics.connections.inc();
+
+    // 发送任务：所有发往客户端的消息都从队列中取出后发送
+    let writer_metrics = metrics.clone();
+    let writer = tokio::spawn(async move {
+        while let Some(msg) = queue.recv().await {
+            writer_metrics.message("out", &msg);
+            if let Err(e) = sender.send(msg).await {
+                writer_metrics.send_errors.inc();
+                error!("[{}] error sending message: {}", id, e);
+                break;
+            }
+        }
+        // 完成关闭握手：回应客户端的关闭帧，或者在服务器主动结束时发送关闭帧
+        let _ = sender.close().await;
+    });
+
+    // 收到关闭通知后置为 true，之后只等待客户端的关闭帧
+    let mut closing = false;
+
+    // 心跳：定期发送 Ping，连续 max_missed_pongs 次没有收到 Pong 就认为对端已经断开。
+    // 第一次 Ping 在一个周期之后发送
+    let mut heartbeat =
+        time::interval_at(Instant::now() + config.ping_interval, config.ping_interval);
+    let mut missed_pongs = 0;
+    // 空闲超时：超过 idle_timeout 没有收到文本或二进制消息就关闭连接
+    let idle = time::sleep(config.idle_timeout);
+    tokio::pin!(idle);
+    // 对端已经失去响应时，发送任务可能阻塞在写入上，不能等待它结束
+    let mut dead = false;
+
     // 处理来自客户端的消息
-    while let Some(msg) = receiver.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = heartbeat.tick() => {
+                if missed_pongs >= config.max_missed_pongs {
+                    info!("[{}] missed {} pongs, dropping connection", ctx.id, missed_pongs);
+                    dead = true;
+                    break;
+                }
+                missed_pongs += 1;
+                // 队列已满说明对端读得很慢或者已经断开，这次 Ping 同样计为未回应
+                let _ = ctx.outbound.try_send(Message::Ping(Vec::new()));
+                continue;
+            }
+            _ = &mut idle, if !closing => {
+                info!("[{}] idle for {}s, closing", ctx.id, config.idle_timeout.as_secs());
+                closing = true;
+                if ctx.outbound.send(close_frame(CloseCode::Away, "idle timeout")).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+            _ = shutdown.recv(), if !closing => {
+                closing = true;
+                let frame = close_frame(CloseCode::Away, "server shutting down");
+                if ctx.outbound.send(frame).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        if let Ok(msg) = &msg {
+            metrics.message("in", msg);
+            // 限流：超过限制时以 1008 关闭连接，不再等待客户端回应关闭帧
+            if !msg.is_close() {
+                if let Err(exceeded) = limit.check(msg.len()) {
+                    info!(
+                        "[{}] {} rate limit exceeded, closing",
+                        ctx.id,
+                        exceeded.as_str()
+                    );
+                    metrics
+                        .limited
+                        .with_label_values(&[exceeded.as_str()])
+                        .inc();
+                    let frame = close_frame(CloseCode::Policy, "rate limit exceeded");
+                    // 队列已满时发送不了关闭帧，直接断开
+                    dead = ctx.outbound.try_send(frame).is_err();
+                    break;
+                }
+            }
+        }
+
+        // 任何 Pong 都说明对端仍然存活，文本和二进制消息才算作活动
+        match &msg {
+            Ok(Message::Pong(_)) => missed_pongs = 0,
+            Ok(Message::Text(_) | Message::Binary(_)) => {
+                idle.as_mut().reset(Instant::now() + config.idle_timeout)
+            }
+            _ => (),
+        }
+
         match msg {
-            Ok(Message::Text(text)) => {
-                // 反转接收到的字符串并返回给客户端
-                let reversed = text.chars().rev().collect::<String>();
-                if let Err(e) = sender.send(Message::Text(reversed)).await {
-                    error!("error sending message: {}", e);
+            Ok(msg @ (Message::Text(_) | Message::Binary(_))) if !closing => {
+                let timer = metrics
+                    .handler_latency
+                    .with_label_values(&[&route.route])
+                    .start_timer();
+                let replies = handler.handle(&ctx, msg).await;
+                timer.observe_duration();
+                for reply in replies {
+                    // 发送任务已经退出时不再继续处理
+                    if ctx.outbound.send(reply).await.is_err() {
+                        break;
+                    }
                 }
             }
             Ok(Message::Close(_)) => break,
             Ok(_) => (),
             Err(e) => {
-                error!("error processing message: {}", e);
+                error!("[{}] error processing message: {}", ctx.id, e);
                 break;
             }
         }
     }
+
+    // 清理处理器中的连接状态，然后等待发送任务把队列中剩余的消息发完
+    handler.on_close(&ctx);
+    metrics.connections.dec();
+    drop(ctx);
+    if dead {
+        writer.abort();
+    }
+    let _ = writer.await;
+}
+
+fn close_frame(code: CloseCode, reason: &'static str) -> Message {
+    Message::Close(Some(CloseFrame {
+        code,
+        reason: reason.into(),
+    }))
 }
@@ -0,0 +1,239 @@
+use log::error;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time;
+use tokio_tungstenite::tungstenite::Message;
+
+// Prometheus 指标，由 --metrics-addr 指定的独立端口以文本格式提供：GET /metrics
+pub struct Metrics {
+    registry: Registry,
+    // 当前已经完成握手的连接数
+    pub connections: IntGauge,
+    // 握手结果：accepted、rejected（404/401/503 等）、failed（协议或 TLS 错误）、timeout（期限内没有完成握手）
+    pub handshakes: IntCounterVec,
+    // 按方向（in/out）和类型（text/binary/ping/pong/close）统计的消息数和负载字节数
+    messages: IntCounterVec,
+    bytes: IntCounterVec,
+    // 发送任务写入失败的次数
+    pub send_errors: IntCounter,
+    // 因超过限制而关闭或拒绝的连接：connection、ip（限流）和 max_connections（连接数上限）
+    pub limited: IntCounterVec,
+    // 处理器处理一条消息的耗时，按路由区分
+    pub handler_latency: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let connections = IntGauge::new(
+            "websocket_active_connections",
+            "Number of open WebSocket connections",
+        )
+        .unwrap();
+        let handshakes = IntCounterVec::new(
+            Opts::new(
+                "websocket_handshakes_total",
+                "WebSocket handshakes by result",
+            ),
+            &["result"],
+        )
+        .unwrap();
+        let messages = IntCounterVec::new(
+            Opts::new(
+                "websocket_messages_total",
+                "WebSocket messages by direction and type",
+            ),
+            &["direction", "type"],
+        )
+        .unwrap();
+        let bytes = IntCounterVec::new(
+            Opts::new(
+                "websocket_message_bytes_total",
+                "WebSocket payload bytes by direction and message type",
+            ),
+            &["direction", "type"],
+        )
+        .unwrap();
+        let send_errors = IntCounter::new(
+            "websocket_send_errors_total",
+            "Errors while sending messages to clients",
+        )
+        .unwrap();
+        let limited = IntCounterVec::new(
+            Opts::new(
+                "websocket_limited_total",
+                "Connections closed or refused because of a limit",
+            ),
+            &["limit"],
+        )
+        .unwrap();
+        let handler_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "websocket_handler_duration_seconds",
+                "Time spent handling one message, by route",
+            )
+            // 处理器通常很快，从 100 微秒开始
+            .buckets(prometheus::exponential_buckets(0.0001, 4.0, 9).unwrap()),
+            &["route"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(connections.clone())).unwrap();
+        registry.register(Box::new(handshakes.clone())).unwrap();
+        registry.register(Box::new(messages.clone())).unwrap();
+        registry.register(Box::new(bytes.clone())).unwrap();
+        registry.register(Box::new(send_errors.clone())).unwrap();
+        registry.register(Box::new(limited.clone())).unwrap();
+        registry
+            .register(Box::new(handler_latency.clone()))
+            .unwrap();
+
+        Metrics {
+            registry,
+            connections,
+            handshakes,
+            messages,
+            bytes,
+            send_errors,
+            limited,
+            handler_latency,
+        }
+    }
+
+    // 记录一条收到（in）或发出（out）的消息
+    pub fn message(&self, direction: &str, msg: &Message) {
+        let kind = match msg {
+            Message::Text(_) => "text",
+            Message::Binary(_) => "binary",
+            Message::Ping(_) => "ping",
+            Message::Pong(_) => "pong",
+            Message::Close(_) => "close",
+        };
+        self.messages.with_label_values(&[direction, kind]).inc();
+        self.bytes
+            .with_label_values(&[direction, kind])
+            .inc_by(msg.len() as u64);
+    }
+
+    pub fn render(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("encoding metrics into a Vec cannot fail");
+        buffer
+    }
+}
+
+// 指标端口上的 HTTP 服务，每个请求处理完就关闭连接
+pub async fn serve(listener: TcpListener, metrics: Arc<Metrics>) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!("error accepting metrics connection: {}", e);
+                time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &metrics).await {
+                error!("error serving metrics to {}: {}", peer, e);
+            }
+        });
+    }
+}
+
+// 只读取请求头，GET /metrics 返回指标，其余路径返回 404
+async fn respond<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    metrics: &Metrics,
+) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut chunk = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        // 请求头过大或者客户端迟迟不发完请求时直接断开
+        let n = time::timeout(Duration::from_secs(5), stream.read(&mut chunk))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+        if n == 0 || request.len() > 8192 {
+            return Ok(());
+        }
+        request.extend_from_slice(&chunk[..n]);
+    }
+
+    let line = String::from_utf8_lossy(&request);
+    let mut parts = line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            TextEncoder::new().format_type().to_string(),
+            metrics.render(),
+        ),
+        _ => (
+            "404 Not Found",
+            String::from("text/plain"),
+            b"not found".to_vec(),
+        ),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    async fn get(metrics: &Metrics, path: &str) -> String {
+        let (server, mut client) = duplex(1 << 16);
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        client.write_all(request.as_bytes()).await.unwrap();
+        respond(server, metrics).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_recorded_metrics() {
+        let metrics = Metrics::new();
+        metrics.connections.inc();
+        metrics.handshakes.with_label_values(&["accepted"]).inc();
+        metrics.message("in", &Message::Text("hello".into()));
+        metrics.message("out", &Message::Binary(vec![0; 3]));
+        metrics
+            .handler_latency
+            .with_label_values(&["/echo"])
+            .observe(0.001);
+
+        let response = get(&metrics, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("websocket_active_connections 1\n"));
+        assert!(response.contains("websocket_handshakes_total{result=\"accepted\"} 1\n"));
+        assert!(
+            response.contains("websocket_message_bytes_total{direction=\"in\",type=\"text\"} 5\n")
+        );
+        assert!(
+            response.contains("websocket_messages_total{direction=\"out\",type=\"binary\"} 1\n")
+        );
+        assert!(response.contains("websocket_handler_duration_seconds_count{route=\"/echo\"} 1\n"));
+
+        assert!(get(&metrics, "/").await.starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::handler::{ConnectionContext, MessageHandler};
+use futures::future::{self, BoxFuture};
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+// 每个房间的广播通道容量，订阅者落后超过这么多条消息时会丢失最旧的消息
+const ROOM_CAPACITY: usize = 256;
+
+// 聊天室：客户端发送文本命令
+//   join <room>             订阅房间
+//   leave <room>            退订房间
+//   publish <room> <text>   向房间的所有订阅者广播 "[room] #id: text"
+// 通过 /chat/{room} 连接时自动加入该房间，发送的文本直接发布到房间，不再解析命令。
+// 每个房间一个 broadcast 通道，每个订阅由一个转发任务把消息放入客户端的发送队列。
+// 发送队列是有界的且使用 try_send，读得慢的客户端只会丢失自己的消息，不会拖住整个房间
+#[derive(Default)]
+pub struct Rooms {
+    rooms: Mutex<HashMap<String, broadcast::Sender<Arc<str>>>>,
+    // 连接编号 -> 房间名 -> 转发任务
+    subscriptions: Mutex<HashMap<u64, HashMap<String, JoinHandle<()>>>>,
+}
+
+enum Command<'a> {
+    Join(&'a str),
+    Leave(&'a str),
+    Publish(&'a str, &'a str),
+}
+
+fn parse_command(text: &str) -> Result<Command<'_>, String> {
+    let (verb, rest) = text
+        .trim_start()
+        .split_once(' ')
+        .unwrap_or((text.trim(), ""));
+    let (room, body) = rest
+        .trim_start()
+        .split_once(' ')
+        .unwrap_or((rest.trim(), ""));
+    let command = match verb {
+        "join" => Command::Join(room),
+        "leave" => Command::Leave(room),
+        "publish" => Command::Publish(room, body),
+        _ => return Err(format!("unknown command {}", verb)),
+    };
+    if room.is_empty() {
+        return Err(format!("{} requires a room name", verb));
+    }
+
+    Ok(command)
+}
+
+impl Rooms {
+    pub fn new() -> Rooms {
+        Rooms::default()
+    }
+
+    fn join(&self, ctx: &ConnectionContext, room: &str) -> String {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let joined = subscriptions.entry(ctx.id).or_default();
+        if joined.contains_key(room) {
+            return format!("already joined {}", room);
+        }
+
+        let mut receiver = self
+            .rooms
+            .lock()
+            .unwrap()
+            .entry(room.to_string())
+            .or_insert_with(|| broadcast::channel(ROOM_CAPACITY).0)
+            .subscribe();
+
+        let outbound = ctx.outbound.clone();
+        let (id, name) = (ctx.id, room.to_string());
+        let forward = tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(text) => match outbound.try_send(Message::Text(text.to_string())) {
+                        Ok(()) => (),
+                        Err(TrySendError::Full(_)) => {
+                            warn!("[{}] send queue full, dropped a message from {}", id, name)
+                        }
+                        Err(TrySendError::Closed(_)) => break,
+                    },
+                    Err(RecvError::Lagged(n)) => {
+                        warn!("[{}] lagged behind {}, skipped {} messages", id, name, n)
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        joined.insert(room.to_string(), forward);
+
+        format!("joined {}", room)
+    }
+
+    fn leave(&self, ctx: &ConnectionContext, room: &str) -> String {
+        let forward = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .get_mut(&ctx.id)
+            .and_then(|joined| joined.remove(room));
+
+        match forward {
+            Some(forward) => {
+                forward.abort();
+                self.remove_if_empty(room);
+                format!("left {}", room)
+            }
+            None => format!("not in {}", room),
+        }
+    }
+
+    fn publish(&self, ctx: &ConnectionContext, room: &str, body: &str) -> Option<String> {
+        let rooms = self.rooms.lock().unwrap();
+        // 没有订阅者时 send 返回错误，消息直接丢弃即可
+        let sender = rooms.get(room)?;
+        let _ = sender.send(format!("[{}] #{}: {}", room, ctx.id, body).into());
+        None
+    }
+
+    // 最后一个订阅者离开后删除房间。转发任务被 abort 后接收端不会立即释放，
+    // 因此只按订阅表判断是否还有人在房间里
+    fn remove_if_empty(&self, room: &str) {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        if !subscriptions
+            .values()
+            .any(|joined| joined.contains_key(room))
+        {
+            self.rooms.lock().unwrap().remove(room);
+        }
+    }
+}
+
+impl MessageHandler for Rooms {
+    fn handle<'a>(
+        &'a self,
+        ctx: &'a ConnectionContext,
+        msg: Message,
+    ) -> BoxFuture<'a, Vec<Message>> {
+        let reply = match msg {
+            Message::Text(text) if ctx.params.contains_key("room") => {
+                self.publish(ctx, &ctx.params["room"], &text)
+            }
+            Message::Text(text) => match parse_command(&text) {
+                Ok(Command::Join(room)) => Some(self.join(ctx, room)),
+                Ok(Command::Leave(room)) => Some(self.leave(ctx, room)),
+                Ok(Command::Publish(room, body)) => self.publish(ctx, room, body),
+                Err(e) => Some(format!("error: {}", e)),
+            },
+            _ => Some("error: expected a text command".to_string()),
+        };
+
+        Box::pin(future::ready(
+            reply.map(Message::Text).into_iter().collect(),
+        ))
+    }
+
+    fn on_open(&self, ctx: &ConnectionContext) {
+        if let Some(room) = ctx.params.get("room") {
+            self.join(ctx, room);
+        }
+    }
+
+    fn on_close(&self, ctx: &ConnectionContext) {
+        let joined = self.subscriptions.lock().unwrap().remove(&ctx.id);
+        for (room, forward) in joined.unwrap_or_default() {
+            forward.abort();
+            self.remove_if_empty(&room);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    fn ctx(id: u64, queue: usize) -> (ConnectionContext, mpsc::Receiver<Message>) {
+        let (outbound, receiver) = mpsc::channel(queue);
+        let ctx = ConnectionContext::new(id, "127.0.0.1:9000".parse().unwrap(), outbound);
+        (ctx, receiver)
+    }
+
+    async fn command(rooms: &Rooms, ctx: &ConnectionContext, text: &str) -> Vec<Message> {
+        rooms.handle(ctx, Message::Text(text.to_string())).await
+    }
+
+    #[tokio::test]
+    async fn fans_out_to_subscribers() {
+        let rooms = Rooms::new();
+        let (alice, mut alice_rx) = ctx(1, 8);
+        let (bob, mut bob_rx) = ctx(2, 8);
+
+        assert_eq!(
+            vec![Message::Text("joined lobby".into())],
+            command(&rooms, &alice, "join lobby").await
+        );
+        command(&rooms, &bob, "join lobby").await;
+        assert!(command(&rooms, &bob, "publish lobby hi all")
+            .await
+            .is_empty());
+
+        let expected = Message::Text("[lobby] #2: hi all".into());
+        assert_eq!(Some(expected.clone()), alice_rx.recv().await);
+        assert_eq!(Some(expected), bob_rx.recv().await);
+
+        command(&rooms, &alice, "leave lobby").await;
+        rooms.on_close(&bob);
+        assert!(rooms.rooms.lock().unwrap().is_empty());
+
+        assert_eq!(
+            vec![Message::Text("error: unknown command shout".into())],
+            command(&rooms, &alice, "shout lobby").await
+        );
+        assert_eq!(
+            vec![Message::Text("error: join requires a room name".into())],
+            command(&rooms, &alice, "join").await
+        );
+    }
+
+    #[tokio::test]
+    async fn room_from_path() {
+        let rooms = Rooms::new();
+        let (mut alice, mut alice_rx) = ctx(1, 8);
+        alice.params.insert("room".into(), "ops".into());
+        let (bob, mut bob_rx) = ctx(2, 8);
+
+        rooms.on_open(&alice);
+        command(&rooms, &bob, "join ops").await;
+        assert!(command(&rooms, &alice, "join other").await.is_empty());
+
+        let expected = Message::Text("[ops] #1: join other".into());
+        assert_eq!(Some(expected.clone()), alice_rx.recv().await);
+        assert_eq!(Some(expected), bob_rx.recv().await);
+    }
+
+    #[tokio::test]
+    async fn slow_reader_does_not_block_room() {
+        let rooms = Rooms::new();
+        let (slow, mut slow_rx) = ctx(1, 1);
+        let (fast, mut fast_rx) = ctx(2, 8);
+        command(&rooms, &slow, "join news").await;
+        command(&rooms, &fast, "join news").await;
+
+        for i in 0..3 {
+            command(&rooms, &fast, &format!("publish news {}", i)).await;
+        }
+
+        for i in 0..3 {
+            let expected = Message::Text(format!("[news] #2: {}", i));
+            assert_eq!(Some(expected), fast_rx.recv().await);
+        }
+        // 慢客户端的队列只能放一条，其余被丢弃
+        assert_eq!(
+            Some(Message::Text("[news] #2: 0".into())),
+            slow_rx.recv().await
+        );
+        assert!(slow_rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::handler::MessageHandler;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request};
+use tokio_tungstenite::tungstenite::http::header::CONTENT_LENGTH;
+use tokio_tungstenite::tungstenite::http::{Response, StatusCode};
+
+// 升级请求的路由：按路径选择处理器，路径中的 {name} 段匹配任意一段并作为参数传给处理器
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+struct Route {
+    pattern: String,
+    segments: Vec<Segment>,
+    handler: Arc<dyn MessageHandler>,
+}
+
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+// 匹配成功的路由，连同从升级请求中解析出的信息
+pub struct Matched {
+    pub handler: Arc<dyn MessageHandler>,
+    // 注册时的路径模式，如 /chat/{room}，用作指标的标签
+    pub route: String,
+    pub path: String,
+    pub params: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    pub fn route(mut self, pattern: &str, handler: Arc<dyn MessageHandler>) -> Router {
+        let segments = segments(pattern)
+            .map(
+                |segment| match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                    Some(name) => Segment::Param(name.to_string()),
+                    None => Segment::Literal(segment.to_string()),
+                },
+            )
+            .collect();
+        self.routes.push(Route {
+            pattern: pattern.to_string(),
+            segments,
+            handler,
+        });
+        self
+    }
+
+    // 按注册顺序匹配，第一个匹配的路由生效
+    pub fn find(&self, request: &Request) -> Option<Matched> {
+        let path = request.uri().path();
+        let parts: Vec<&str> = segments(path).collect();
+
+        self.routes.iter().find_map(|route| {
+            if route.segments.len() != parts.len() {
+                return None;
+            }
+
+            let mut params = HashMap::new();
+            for (segment, part) in route.segments.iter().zip(&parts) {
+                match segment {
+                    Segment::Literal(literal) if literal == part => (),
+                    Segment::Param(name) if !part.is_empty() => {
+                        params.insert(name.clone(), percent_decode(part, false));
+                    }
+                    _ => return None,
+                }
+            }
+
+            Some(Matched {
+                handler: route.handler.clone(),
+                route: route.pattern.clone(),
+                path: path.to_string(),
+                params,
+                query: parse_query(request.uri().query().unwrap_or("")),
+            })
+        })
+    }
+}
+
+// "/" 没有任何段，末尾的 "/" 也忽略
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.trim_matches('/').split('/').filter(|s| !s.is_empty())
+}
+
+pub fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key, true), percent_decode(value, true))
+        })
+        .collect()
+}
+
+// 查询字符串中的 + 表示空格，路径中则没有这个约定
+fn percent_decode(text: &str, plus_as_space: bool) -> String {
+    let bytes = text.as_bytes();
+    let hex = |i: usize| bytes.get(i).and_then(|&b| (b as char).to_digit(16));
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], hex(i + 1), hex(i + 2)) {
+            (b'%', Some(high), Some(low)) => {
+                decoded.push((high * 16 + low) as u8);
+                i += 2;
+            }
+            (b'+', _, _) if plus_as_space => decoded.push(b' '),
+            (byte, _, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// 拒绝升级时返回的 HTTP 响应
+pub fn reject(status: StatusCode, body: &str) -> ErrorResponse {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_LENGTH, body.len())
+        .body(Some(body.to_string()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::{Echo, Reverse};
+
+    fn request(uri: &str) -> Request {
+        Request::builder().uri(uri).body(()).unwrap()
+    }
+
+    #[test]
+    fn matches_paths_and_params() {
+        let router = Router::new()
+            .route("/", Arc::new(Echo))
+            .route("/reverse", Arc::new(Reverse))
+            .route("/chat/{room}", Arc::new(Echo));
+
+        assert_eq!("/", router.find(&request("/")).unwrap().path);
+        assert!(router.find(&request("/reverse/")).is_some());
+
+        let matched = router
+            .find(&request("/chat/tea%20room?token=a%2Bb&x"))
+            .unwrap();
+        assert_eq!("tea room", matched.params["room"]);
+        assert_eq!("/chat/{room}", matched.route);
+        assert_eq!("a+b", matched.query["token"]);
+        assert_eq!("", matched.query["x"]);
+
+        let matched = router.find(&request("/chat/a+b?q=a+b")).unwrap();
+        assert_eq!("a+b", matched.params["room"]);
+        assert_eq!("a b", matched.query["q"]);
+
+        assert!(router.find(&request("/chat")).is_none());
+        assert!(router.find(&request("/chat/a/b")).is_none());
+        assert!(router.find(&request("/nope")).is_none());
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time;
+
+// 优雅关闭：Drain 通知所有连接开始关闭，并等待它们全部结束。
+// 每个连接持有一个 Watch，其中的 mpsc::Sender 只用于计数：
+// 所有 Watch 都被丢弃后 done.recv() 返回 None，说明连接已经全部结束
+pub struct Drain {
+    signal: watch::Sender<bool>,
+    done_sender: mpsc::Sender<()>,
+    done: mpsc::Receiver<()>,
+}
+
+pub struct Watch {
+    signal: watch::Receiver<bool>,
+    _done: mpsc::Sender<()>,
+}
+
+impl Drain {
+    pub fn new() -> Drain {
+        let (signal, _) = watch::channel(false);
+        let (done_sender, done) = mpsc::channel(1);
+        Drain {
+            signal,
+            done_sender,
+            done,
+        }
+    }
+
+    pub fn watch(&self) -> Watch {
+        Watch {
+            signal: self.signal.subscribe(),
+            _done: self.done_sender.clone(),
+        }
+    }
+
+    // 通知所有连接关闭，最多等待 timeout；返回是否所有连接都已结束
+    pub async fn drain(self, timeout: Duration) -> bool {
+        let Drain {
+            signal,
+            done_sender,
+            mut done,
+        } = self;
+
+        let _ = signal.send(true);
+        drop(done_sender);
+        time::timeout(timeout, done.recv()).await.is_ok()
+    }
+}
+
+impl Watch {
+    // 等待关闭通知；Drain 被丢弃也视为关闭
+    pub async fn recv(&mut self) {
+        let _ = self.signal.wait_for(|&shutdown| shutdown).await;
+    }
+}
+
+// 等待 SIGINT（Ctrl-C）或 SIGTERM
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate =
+            signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => (),
+            _ = terminate.recv() => (),
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn waits_for_connections_to_finish() {
+        let drain = Drain::new();
+        let mut watch = drain.watch();
+        tokio::spawn(async move {
+            watch.recv().await;
+            time::sleep(Duration::from_millis(10)).await;
+        });
+        assert!(drain.drain(Duration::from_secs(5)).await);
+
+        // 不响应关闭通知的连接会导致超时
+        let drain = Drain::new();
+        let stuck = drain.watch();
+        assert!(!drain.drain(Duration::from_millis(10)).await);
+        drop(stuck);
+    }
+}
@@ -0,0 +1,71 @@
+use log::{error, info};
+use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+// TLS 终止：从 PEM 文件加载证书链和私钥，收到 SIGHUP 时重新加载。
+// 重新加载只影响之后的新连接，已建立的连接继续使用原来的证书
+pub struct Tls {
+    cert: PathBuf,
+    key: PathBuf,
+    acceptor: RwLock<TlsAcceptor>,
+}
+
+impl Tls {
+    pub fn load(cert: PathBuf, key: PathBuf) -> Result<Tls, String> {
+        let acceptor = acceptor(&cert, &key)?;
+        Ok(Tls {
+            cert,
+            key,
+            acceptor: RwLock::new(acceptor),
+        })
+    }
+
+    // 当前使用的 acceptor，内部是 Arc，克隆的开销很小
+    pub fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor.read().unwrap().clone()
+    }
+
+    // 加载失败时保留原来的证书，避免一次写错文件就让服务器无法接受新连接
+    pub fn reload(&self) -> Result<(), String> {
+        let acceptor = acceptor(&self.cert, &self.key)?;
+        *self.acceptor.write().unwrap() = acceptor;
+        Ok(())
+    }
+
+    // 在后台等待 SIGHUP 并重新加载证书
+    #[cfg(unix)]
+    pub fn reload_on_sighup(self: Arc<Self>) -> std::io::Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = signal(SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                match self.reload() {
+                    Ok(()) => info!("reloaded TLS certificate from {}", self.cert.display()),
+                    Err(e) => error!("failed to reload TLS certificate: {}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+fn acceptor(cert: &PathBuf, key: &PathBuf) -> Result<TlsAcceptor, String> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("{}: {}", cert.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("{}: no certificates found", cert.display()));
+    }
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|e| format!("{}: {}", key.display(), e))?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("invalid TLS certificate or key: {}", e))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
@@ -0,0 +1,232 @@
+// 集成测试：启动编译好的 web-sockets，用真实的 TCP 和 WebSocket 客户端连接
+
+use futures::{SinkExt, StreamExt};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    // 在空闲端口上启动服务器，等到日志中出现 listening on 再返回，
+    // 不用探测连接，以免探测连接占用 --max-connections 的许可
+    fn start(args: &[&str]) -> Server {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let addr = format!("127.0.0.1:{}", port);
+        let mut child = Command::new(env!("CARGO_BIN_EXE_web-sockets"))
+            .arg(&addr)
+            .args(args)
+            .env("RUST_LOG", "info")
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to start web-sockets");
+
+        let mut lines = BufReader::new(child.stderr.take().unwrap()).lines();
+        loop {
+            match lines.next() {
+                Some(Ok(line)) if line.contains("listening on") => break,
+                Some(Ok(_)) => (),
+                _ => panic!("web-sockets exited before listening"),
+            }
+        }
+        // 继续读取日志，避免管道写满阻塞服务器
+        std::thread::spawn(move || lines.for_each(drop));
+
+        Server { child, addr }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("ws://{}{}", self.addr, path)
+    }
+
+    // 与 Ctrl-C 一样触发优雅关闭
+    fn terminate(&self) {
+        let status = Command::new("kill")
+            .args(["-TERM", &self.child.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// 握手被拒绝时返回 HTTP 状态码
+async fn rejected_status(url: String) -> u16 {
+    match connect_async(url).await {
+        Err(WsError::Http(response)) => response.status().as_u16(),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("upgrade was not rejected"),
+    }
+}
+
+// 手工完成升级握手，之后不再读取，因此也不会回应服务器的 Ping
+async fn raw_upgrade(addr: &str) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "GET /echo HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        addr
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    // 逐字节读取响应头，不多读后面的帧
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).await.unwrap();
+        head.push(byte[0]);
+    }
+    assert!(head.starts_with(b"HTTP/1.1 101"));
+    stream
+}
+
+// 等待对端关闭 TCP 连接，返回之前收到的字节数
+async fn read_until_closed(stream: &mut TcpStream, limit: Duration) -> usize {
+    let mut total = 0;
+    let mut buf = [0; 1024];
+    loop {
+        let n = time::timeout(limit, stream.read(&mut buf))
+            .await
+            .expect("connection was not closed in time")
+            .unwrap_or(0);
+        if n == 0 {
+            return total;
+        }
+        total += n;
+    }
+}
+
+#[tokio::test]
+async fn silent_sockets_do_not_hold_the_connection_limit() {
+    let server = Server::start(&["--max-connections", "1", "--idle-timeout", "1"]);
+
+    // 只建立 TCP 连接、不发送握手请求的客户端占着唯一的许可
+    let mut silent = TcpStream::connect(&server.addr).await.unwrap();
+    time::sleep(Duration::from_millis(200)).await;
+
+    // 超过上限的连接收到 503，而不是连接被重置
+    assert_eq!(503, rejected_status(server.url("/echo")).await);
+
+    // 握手期限（idle_timeout）到了之后断开静默的连接并归还许可
+    assert_eq!(
+        0,
+        read_until_closed(&mut silent, Duration::from_secs(5)).await
+    );
+    connect_async(server.url("/echo"))
+        .await
+        .expect("connection after the silent socket was dropped");
+}
+
+#[tokio::test]
+async fn drops_clients_that_never_finish_the_handshake() {
+    let server = Server::start(&["--idle-timeout", "1"]);
+
+    let mut silent = TcpStream::connect(&server.addr).await.unwrap();
+    let start = Instant::now();
+    assert_eq!(
+        0,
+        read_until_closed(&mut silent, Duration::from_secs(5)).await
+    );
+    assert!(start.elapsed() >= Duration::from_millis(900));
+}
+
+#[tokio::test]
+async fn drops_clients_that_miss_pongs() {
+    let server = Server::start(&["--ping-interval", "1", "--max-missed-pongs", "2"]);
+
+    // 第 1、2 秒各发一次 Ping，第 3 秒发现两次都没有回应后断开
+    let mut stream = raw_upgrade(&server.addr).await;
+    let start = Instant::now();
+    let received = read_until_closed(&mut stream, Duration::from_secs(10)).await;
+    let elapsed = start.elapsed();
+    assert_eq!(4, received, "two empty ping frames");
+    assert!(elapsed >= Duration::from_millis(2500), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn closes_with_going_away_on_shutdown() {
+    let server = Server::start(&[]);
+    let (mut ws, _) = connect_async(server.url("/echo")).await.unwrap();
+
+    server.terminate();
+    let msg = time::timeout(Duration::from_secs(5), ws.next())
+        .await
+        .expect("no close frame before the timeout");
+    match msg {
+        Some(Ok(Message::Close(Some(frame)))) => assert_eq!(CloseCode::Away, frame.code),
+        other => panic!("expected a close frame, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn rejects_unknown_paths_and_missing_tokens() {
+    let tokens = format!(
+        "{}/tokens-{}",
+        env!("CARGO_TARGET_TMPDIR"),
+        std::process::id()
+    );
+    std::fs::write(&tokens, "secret alice\n").unwrap();
+    let server = Server::start(&["--auth-tokens", &tokens]);
+
+    // 路由先于认证检查
+    assert_eq!(404, rejected_status(server.url("/nope")).await);
+    assert_eq!(401, rejected_status(server.url("/echo")).await);
+    assert_eq!(401, rejected_status(server.url("/echo?token=wrong")).await);
+
+    let (mut ws, _) = connect_async(server.url("/echo?token=secret"))
+        .await
+        .unwrap();
+    ws.send(Message::Text("hi".into())).await.unwrap();
+    assert_eq!(
+        Some(Message::Text("hi".into())),
+        ws.next().await.and_then(Result::ok)
+    );
+    std::fs::remove_file(tokens).unwrap();
+}
+
+#[tokio::test]
+async fn closes_with_policy_violation_over_the_rate_limit() {
+    let server = Server::start(&["--rate-messages", "2"]);
+    let (mut ws, _) = connect_async(server.url("/echo")).await.unwrap();
+
+    for i in 0..5 {
+        ws.send(Message::Text(i.to_string())).await.unwrap();
+    }
+
+    // 前两条消息正常回显，第三条超过限制，连接以 1008 关闭
+    let mut echoed = Vec::new();
+    loop {
+        let msg = time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("no close frame before the timeout");
+        match msg {
+            Some(Ok(Message::Text(text))) => echoed.push(text),
+            Some(Ok(Message::Close(Some(frame)))) => {
+                assert_eq!(CloseCode::Policy, frame.code);
+                break;
+            }
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+    }
+    assert_eq!(vec!["0", "1"], echoed);
+}